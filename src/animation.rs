@@ -0,0 +1,269 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use image::gif::GifDecoder;
+use image::io::Reader;
+use image::png::PngEncoder;
+use image::{AnimationDecoder, ColorType, ImageFormat};
+
+use crate::color::Rgb;
+
+/// Shortest delay between frames, browsers showing faster GIFs at 10 fps.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// Frames of an animation composited over the whole canvas, as packed 8-bit
+/// RGBA pixels along with how long each one shows.
+pub struct Animation {
+    pub width: usize,
+    pub height: usize,
+    pub frames: Vec<(Vec<u8>, Duration)>,
+}
+
+/// Reads the frames of an animated GIF, `None` for still images.
+pub fn read(path: &Path) -> Result<Option<Animation>, Box<dyn Error>> {
+    if Reader::open(path)?.with_guessed_format()?.format() != Some(ImageFormat::Gif) {
+        return Ok(None);
+    }
+
+    let decoder = GifDecoder::new(BufReader::new(File::open(path)?))?;
+    let frames = decoder.into_frames().collect_frames()?;
+    if frames.len() < 2 {
+        return Ok(None);
+    }
+
+    let (width, height) = frames[0].buffer().dimensions();
+    let frames = frames
+        .into_iter()
+        .map(|frame| {
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = Duration::from_millis((numer / denom.max(1)) as u64);
+            let delay = if delay < MIN_DELAY {
+                DEFAULT_DELAY
+            } else {
+                delay
+            };
+            (frame.into_buffer().into_raw(), delay)
+        })
+        .collect();
+
+    Ok(Some(Animation {
+        width: width as usize,
+        height: height as usize,
+        frames,
+    }))
+}
+
+/// How an animation is exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+    /// One numbered PNG per frame.
+    Frames,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Gif, Format::Apng, Format::Frames];
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Gif
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Gif => "GIF",
+                Self::Apng => "APNG",
+                Self::Frames => "PNG frames",
+            }
+        )
+    }
+}
+
+/// Encodes looping RGBA frames as a GIF, all of their colors being in
+/// `palette` which is shared by the frames.
+pub fn encode_gif(
+    (width, height): (usize, usize),
+    frames: &[(Vec<u8>, Duration)],
+    palette: &[Rgb],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    // The index past the palette is the transparent one
+    if palette.len() >= 256 {
+        return Err("a GIF holds at most 255 colors".into());
+    }
+    let transparent = palette.len() as u8;
+
+    let mut global_palette = palette
+        .iter()
+        .flat_map(|color| vec![color.r, color.g, color.b])
+        .collect::<Vec<_>>();
+    global_palette.extend_from_slice(&[0, 0, 0]);
+
+    let lookup = palette
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, &color)| (color, i as u8))
+        .collect::<HashMap<_, _>>();
+
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(
+            &mut gif,
+            u16::try_from(width)?,
+            u16::try_from(height)?,
+            &global_palette,
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+
+        for (pixels, delay) in frames {
+            let indices = pixels
+                .chunks_exact(4)
+                .map(|px| {
+                    if px[3] == 0 {
+                        Ok(transparent)
+                    } else {
+                        lookup
+                            .get(&Rgb::new(px[0], px[1], px[2]))
+                            .copied()
+                            .ok_or("frame color missing from the palette")
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut frame = gif::Frame::default();
+            frame.width = width as u16;
+            frame.height = height as u16;
+            frame.buffer = Cow::Owned(indices);
+            frame.transparent = Some(transparent);
+            frame.dispose = gif::DisposalMethod::Background;
+            frame.delay = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+            encoder.write_frame(&frame)?;
+        }
+    }
+
+    Ok(gif)
+}
+
+/// Encodes looping RGBA frames as an APNG.
+///
+/// Each frame is encoded as a PNG of its own whose image data is then moved
+/// into the animation chunks, the first frame being the default image.
+pub fn encode_apng(
+    (width, height): (usize, usize),
+    frames: &[(Vec<u8>, Duration)],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut apng = PNG_SIGNATURE.to_vec();
+    let mut sequence = 0u32;
+
+    for (k, (pixels, delay)) in frames.iter().enumerate() {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).encode(pixels, width as u32, height as u32, ColorType::Rgba8)?;
+
+        let chunks = chunks(&png)?;
+        if k == 0 {
+            let (_, header) = chunks
+                .iter()
+                .find(|(kind, _)| kind == b"IHDR")
+                .ok_or("missing PNG header")?;
+            write_chunk(&mut apng, b"IHDR", header);
+
+            let mut control = Vec::with_capacity(8);
+            control.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+            // Loops forever
+            control.extend_from_slice(&0u32.to_be_bytes());
+            write_chunk(&mut apng, b"acTL", &control);
+        }
+
+        let mut control = Vec::with_capacity(26);
+        control.extend_from_slice(&sequence.to_be_bytes());
+        control.extend_from_slice(&(width as u32).to_be_bytes());
+        control.extend_from_slice(&(height as u32).to_be_bytes());
+        control.extend_from_slice(&0u32.to_be_bytes());
+        control.extend_from_slice(&0u32.to_be_bytes());
+        let delay = delay.as_millis().min(u16::MAX as u128) as u16;
+        control.extend_from_slice(&delay.to_be_bytes());
+        control.extend_from_slice(&1000u16.to_be_bytes());
+        // Neither disposed nor blended, each frame covers the whole canvas
+        control.extend_from_slice(&[0, 0]);
+        write_chunk(&mut apng, b"fcTL", &control);
+        sequence += 1;
+
+        for (kind, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+            if k == 0 {
+                write_chunk(&mut apng, kind, data);
+            } else {
+                let mut frame_data = Vec::with_capacity(4 + data.len());
+                frame_data.extend_from_slice(&sequence.to_be_bytes());
+                frame_data.extend_from_slice(data);
+                write_chunk(&mut apng, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+    }
+
+    write_chunk(&mut apng, b"IEND", &[]);
+    Ok(apng)
+}
+
+/// Path of frame `i` when exporting numbered frames to `path`.
+pub fn numbered(path: &Path, i: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{:03}.png", stem, i))
+}
+
+/// Type of a PNG chunk along with its data.
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits a PNG into its chunks.
+fn chunks(png: &[u8]) -> Result<Vec<Chunk<'_>>, Box<dyn Error>> {
+    let mut chunks = vec![];
+    let mut rest = png.get(PNG_SIGNATURE.len()..).ok_or("truncated PNG")?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let kind = [rest[4], rest[5], rest[6], rest[7]];
+        let data = rest.get(8..8 + len).ok_or("truncated PNG chunk")?;
+        chunks.push((kind, data));
+        rest = &rest[(12 + len).min(rest.len())..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc32(&[&kind[..], data]).to_be_bytes());
+}
+
+/// The CRC-32 of the concatenated `parts`, as PNG chunks carry.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in parts.iter().flat_map(|part| part.iter()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
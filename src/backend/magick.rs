@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::ffi::CString;
+use std::path::Path;
+use std::sync::Once;
+
+use magick_rust::{bindings, magick_wand_genesis, MagickWand, PixelWand};
+
+use super::{Filter, ImageBackend};
+use crate::color::Rgb;
+use crate::params::{Channel, Levels};
+
+static INIT_IMAGE_MAGICK: Once = Once::new();
+
+/// ImageMagick backed image, through MagickWand.
+pub struct Magick {
+    wand: MagickWand,
+}
+
+impl Magick {
+    fn new() -> Self {
+        INIT_IMAGE_MAGICK.call_once(|| {
+            magick_wand_genesis();
+            // Multi-threaded clustering sums floats in a varying order, which
+            // breaks reproducible renders
+            unsafe { bindings::SetMagickResourceLimit(bindings::ResourceType_ThreadResource, 1) };
+        });
+
+        Self {
+            wand: MagickWand::new(),
+        }
+    }
+
+    fn set_artifact(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let (c_key, c_value) = (CString::new(key)?, CString::new(value)?);
+        match unsafe {
+            bindings::MagickSetImageArtifact(self.wand.wand, c_key.as_ptr(), c_value.as_ptr())
+        } {
+            bindings::MagickBooleanType_MagickTrue => Ok(()),
+            _ => Err(format!("failed to set artifact {}", key).into()),
+        }
+    }
+
+    fn set_option(&self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let (c_key, c_value) = (CString::new(key)?, CString::new(value)?);
+        match unsafe { bindings::MagickSetOption(self.wand.wand, c_key.as_ptr(), c_value.as_ptr()) }
+        {
+            bindings::MagickBooleanType_MagickTrue => Ok(()),
+            _ => Err(format!("failed to set option {}", key).into()),
+        }
+    }
+}
+
+impl ImageBackend for Magick {
+    fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let image = Self::new();
+        image.wand.read_image(path.to_string_lossy().as_ref())?;
+        check(
+            unsafe { bindings::MagickAutoOrientImage(image.wand.wand) },
+            "auto orient",
+        )?;
+        Ok(image)
+    }
+
+    fn from_rgb(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let image = Self::new();
+        image.wand.read_image_blob(&ppm(width, height, pixels))?;
+        Ok(image)
+    }
+
+    fn from_rgba(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let image = Self::new();
+        image.wand.read_image_blob(&pam(width, height, pixels))?;
+        Ok(image)
+    }
+
+    fn width(&self) -> usize {
+        self.wand.get_image_width()
+    }
+
+    fn height(&self) -> usize {
+        self.wand.get_image_height()
+    }
+
+    fn resize(&mut self, width: usize, height: usize, filter: Filter) {
+        let filter = match filter {
+            Filter::Point => bindings::FilterType_PointFilter,
+        };
+        self.wand.resize_image(width, height, filter);
+    }
+
+    fn rotate(&mut self, quarter_turns: u8) -> Result<(), Box<dyn Error>> {
+        let background = PixelWand::new();
+        let degrees = (quarter_turns % 4) as f64 * 90.0;
+        check(
+            unsafe { bindings::MagickRotateImage(self.wand.wand, background.wand, degrees) },
+            "rotate",
+        )
+    }
+
+    fn flip(&mut self, horizontal: bool) -> Result<(), Box<dyn Error>> {
+        if horizontal {
+            check(unsafe { bindings::MagickFlopImage(self.wand.wand) }, "flop")
+        } else {
+            check(unsafe { bindings::MagickFlipImage(self.wand.wand) }, "flip")
+        }
+    }
+
+    fn crop(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        check(
+            unsafe {
+                bindings::MagickCropImage(self.wand.wand, width, height, x as isize, y as isize)
+            },
+            "crop",
+        )?;
+
+        // As `+repage` does, or the PNG would keep the offset of the crop
+        let page = CString::new("")?;
+        check(
+            unsafe { bindings::MagickResetImagePage(self.wand.wand, page.as_ptr()) },
+            "reset page",
+        )
+    }
+
+    fn level(&mut self, channel: Option<Channel>, levels: Levels) -> Result<(), Box<dyn Error>> {
+        let mask = channel.map(|channel| unsafe {
+            bindings::MagickSetImageChannelMask(self.wand.wand, channel_type(channel))
+        });
+
+        let leveled = self.wand.level_image(
+            levels.black as f64 / 100.0,
+            levels.gamma as f64 / 100.0,
+            levels.white as f64 / 100.0,
+        );
+
+        if let Some(mask) = mask {
+            unsafe { bindings::MagickSetImageChannelMask(self.wand.wand, mask) };
+        }
+
+        Ok(leveled?)
+    }
+
+    fn modulate(&mut self, brightness: u8, saturation: u8, hue: u8) -> Result<(), Box<dyn Error>> {
+        Ok(self
+            .wand
+            .modulate_image(brightness as f64, saturation as f64, hue as f64)?)
+    }
+
+    fn contrast(&mut self, contrast: f64) -> Result<(), Box<dyn Error>> {
+        check(
+            unsafe { bindings::MagickBrightnessContrastImage(self.wand.wand, 0.0, contrast) },
+            "change contrast",
+        )
+    }
+
+    fn sigmoidal_contrast(
+        &mut self,
+        sharpen: bool,
+        strength: f64,
+        midpoint: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let sharpen = if sharpen {
+            bindings::MagickBooleanType_MagickTrue
+        } else {
+            bindings::MagickBooleanType_MagickFalse
+        };
+        check(
+            unsafe {
+                bindings::MagickSigmoidalContrastImage(
+                    self.wand.wand,
+                    sharpen,
+                    strength,
+                    midpoint * quantum_range(),
+                )
+            },
+            "change sigmoidal contrast",
+        )
+    }
+
+    fn unsharp(&mut self, sigma: f64, amount: f64, threshold: f64) -> Result<(), Box<dyn Error>> {
+        check(
+            unsafe {
+                bindings::MagickUnsharpMaskImage(self.wand.wand, 0.0, sigma, amount, threshold)
+            },
+            "unsharp",
+        )
+    }
+
+    fn blur(&mut self, sigma: f64) -> Result<(), Box<dyn Error>> {
+        check(
+            unsafe { bindings::MagickGaussianBlurImage(self.wand.wand, 0.0, sigma) },
+            "blur",
+        )
+    }
+
+    fn posterize(&mut self, levels: u8) -> Result<(), Box<dyn Error>> {
+        check(
+            unsafe {
+                bindings::MagickPosterizeImage(
+                    self.wand.wand,
+                    levels as usize,
+                    bindings::DitherMethod_NoDitherMethod,
+                )
+            },
+            "posterize",
+        )
+    }
+
+    fn quantize(&mut self, seeds: &[Rgb]) -> Result<(), Box<dyn Error>> {
+        let seed_colors = seeds
+            .iter()
+            .map(Rgb::to_string)
+            .collect::<Vec<_>>()
+            .join(";");
+        self.set_artifact("kmeans:seed-colors", &seed_colors)?;
+
+        Ok(self.wand.kmeans(seeds.len(), 100, 0.01)?)
+    }
+
+    fn rgb(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self
+            .wand
+            .export_image_pixels(0, 0, self.width(), self.height(), "RGB")
+            .ok_or("failed to export pixels")?)
+    }
+
+    fn rgba(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self
+            .wand
+            .export_image_pixels(0, 0, self.width(), self.height(), "RGBA")
+            .ok_or("failed to export pixels")?)
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        // Timestamps would make identical renders differ
+        self.set_option("png:exclude-chunk", "date,time")?;
+
+        Ok(self.wand.write_image_blob("png")?)
+    }
+}
+
+/// Wraps packed 8-bit RGB pixels into a binary PPM.
+fn ppm(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    ppm.extend_from_slice(pixels);
+    ppm
+}
+
+/// Wraps packed 8-bit RGBA pixels into a PAM.
+fn pam(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut pam = format!(
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+        width, height
+    )
+    .into_bytes();
+    pam.extend_from_slice(pixels);
+    pam
+}
+
+/// Maximum value of a channel, which depends on how ImageMagick was built.
+fn quantum_range() -> f64 {
+    let mut range = 0;
+    unsafe { bindings::MagickGetQuantumRange(&mut range) };
+    range as f64
+}
+
+fn check(result: bindings::MagickBooleanType, action: &str) -> Result<(), Box<dyn Error>> {
+    match result {
+        bindings::MagickBooleanType_MagickTrue => Ok(()),
+        _ => Err(format!("failed to {}", action).into()),
+    }
+}
+
+fn channel_type(channel: Channel) -> bindings::ChannelType {
+    match channel {
+        Channel::Red => bindings::ChannelType_RedChannel,
+        Channel::Green => bindings::ChannelType_GreenChannel,
+        Channel::Blue => bindings::ChannelType_BlueChannel,
+    }
+}
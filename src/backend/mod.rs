@@ -0,0 +1,104 @@
+//! Image processing primitives the pipeline is built upon.
+//!
+//! The pure Rust backend is used by default, the ImageMagick one being
+//! available through the `magick` feature.
+
+#[cfg(feature = "magick")]
+mod magick;
+#[cfg(not(feature = "magick"))]
+mod native;
+
+use std::error::Error;
+use std::path::Path;
+
+use crate::color::Rgb;
+use crate::params::{Channel, Levels};
+
+#[cfg(feature = "magick")]
+pub use self::magick::Magick as Backend;
+#[cfg(not(feature = "magick"))]
+pub use self::native::Native as Backend;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Repeats the nearest pixel, for upscaling pixel art.
+    Point,
+}
+
+pub trait ImageBackend {
+    /// Reads the image at `path`, oriented as its EXIF metadata tells.
+    fn read(path: &Path) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized;
+
+    /// Creates an image from packed 8-bit RGB pixels.
+    fn from_rgb(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized;
+
+    /// Creates an image from packed 8-bit RGBA pixels.
+    fn from_rgba(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized;
+
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    fn resize(&mut self, width: usize, height: usize, filter: Filter);
+
+    /// Rotates clockwise by `quarter_turns` times 90 degrees.
+    fn rotate(&mut self, quarter_turns: u8) -> Result<(), Box<dyn Error>>;
+
+    /// Mirrors left to right with `horizontal`, top to bottom otherwise.
+    fn flip(&mut self, horizontal: bool) -> Result<(), Box<dyn Error>>;
+
+    fn crop(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Applies levels to a single channel, or to all of them with `None`.
+    fn level(&mut self, channel: Option<Channel>, levels: Levels) -> Result<(), Box<dyn Error>>;
+
+    /// Scales brightness and saturation, and rotates hue, all in percent
+    /// with 100 leaving the image untouched.
+    fn modulate(&mut self, brightness: u8, saturation: u8, hue: u8) -> Result<(), Box<dyn Error>>;
+
+    /// Linear contrast change, from -100 for a flat gray to 100.
+    fn contrast(&mut self, contrast: f64) -> Result<(), Box<dyn Error>>;
+
+    /// Sigmoidal contrast change of `strength` around `midpoint`, in
+    /// `[0, 1]`, increasing contrast with `sharpen` and reducing it otherwise.
+    fn sigmoidal_contrast(
+        &mut self,
+        sharpen: bool,
+        strength: f64,
+        midpoint: f64,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Adds back `amount` times the difference to a gaussian blur of `sigma`
+    /// wherever it exceeds `threshold`, in `[0, 1]`.
+    fn unsharp(&mut self, sigma: f64, amount: f64, threshold: f64) -> Result<(), Box<dyn Error>>;
+
+    fn blur(&mut self, sigma: f64) -> Result<(), Box<dyn Error>>;
+
+    /// Reduces each channel to `levels` evenly spaced values.
+    fn posterize(&mut self, levels: u8) -> Result<(), Box<dyn Error>>;
+
+    /// Runs a k-means clustering starting from `seeds`, replacing each pixel
+    /// with its cluster color.
+    fn quantize(&mut self, seeds: &[Rgb]) -> Result<(), Box<dyn Error>>;
+
+    /// Packed 8-bit RGB pixels of the image.
+    fn rgb(&self) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Packed 8-bit RGBA pixels of the image, opaque when it has no alpha.
+    fn rgba(&self) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    /// Encodes the image as a PNG, deterministically.
+    fn encode_png(&self) -> Result<Vec<u8>, Box<dyn Error>>;
+}
@@ -0,0 +1,360 @@
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use exif::{In, Reader, Tag};
+
+use image::imageops::{self, FilterType};
+use image::png::PngEncoder;
+use image::{ColorType, Rgba, RgbaImage};
+
+use super::{Filter, ImageBackend};
+use crate::color::{ColorSpace, Metric, Rgb};
+use crate::palette;
+use crate::params::{Channel, Levels};
+
+/// Pure Rust image, through the `image` crate.
+pub struct Native {
+    image: RgbaImage,
+}
+
+impl ImageBackend for Native {
+    fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut image = Self {
+            image: image::open(path)?.to_rgba8(),
+        };
+
+        match orientation(path) {
+            2 => image.flip(true)?,
+            3 => image.rotate(2)?,
+            4 => image.flip(false)?,
+            5 => {
+                image.rotate(1)?;
+                image.flip(true)?;
+            }
+            6 => image.rotate(1)?,
+            7 => {
+                image.rotate(3)?;
+                image.flip(true)?;
+            }
+            8 => image.rotate(3)?,
+            _ => (),
+        }
+
+        Ok(image)
+    }
+
+    fn from_rgb(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if pixels.len() != width * height * 3 {
+            return Err("pixels do not match the image size".into());
+        }
+        let image = RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+            let i = (y as usize * width + x as usize) * 3;
+            Rgba([pixels[i], pixels[i + 1], pixels[i + 2], 255])
+        });
+        Ok(Self { image })
+    }
+
+    fn from_rgba(width: usize, height: usize, pixels: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let image = RgbaImage::from_raw(width as u32, height as u32, pixels.to_vec())
+            .ok_or("pixels do not match the image size")?;
+        Ok(Self { image })
+    }
+
+    fn width(&self) -> usize {
+        self.image.width() as usize
+    }
+
+    fn height(&self) -> usize {
+        self.image.height() as usize
+    }
+
+    fn resize(&mut self, width: usize, height: usize, filter: Filter) {
+        let filter = match filter {
+            Filter::Point => FilterType::Nearest,
+        };
+        self.image = imageops::resize(
+            &self.image,
+            width.max(1) as u32,
+            height.max(1) as u32,
+            filter,
+        );
+    }
+
+    fn rotate(&mut self, quarter_turns: u8) -> Result<(), Box<dyn Error>> {
+        match quarter_turns % 4 {
+            1 => self.image = imageops::rotate90(&self.image),
+            2 => self.image = imageops::rotate180(&self.image),
+            3 => self.image = imageops::rotate270(&self.image),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn flip(&mut self, horizontal: bool) -> Result<(), Box<dyn Error>> {
+        if horizontal {
+            imageops::flip_horizontal_in_place(&mut self.image);
+        } else {
+            imageops::flip_vertical_in_place(&mut self.image);
+        }
+        Ok(())
+    }
+
+    fn crop(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        self.image =
+            imageops::crop_imm(&self.image, x as u32, y as u32, width as u32, height as u32)
+                .to_image();
+        Ok(())
+    }
+
+    fn level(&mut self, channel: Option<Channel>, levels: Levels) -> Result<(), Box<dyn Error>> {
+        let black = levels.black as f64 / 100.0;
+        let white = levels.white as f64 / 100.0;
+        let gamma = levels.gamma as f64 / 100.0;
+        if gamma <= 0.0 {
+            return Err("gamma must be positive".into());
+        }
+
+        // Same mapping as ImageMagick's -level
+        let mut table = [0u8; 256];
+        for (value, level) in table.iter_mut().enumerate() {
+            let scaled = if white > black {
+                ((value as f64 / 255.0 - black) / (white - black)).max(0.0)
+            } else if value as f64 / 255.0 >= black {
+                1.0
+            } else {
+                0.0
+            };
+            *level = (scaled.powf(1.0 / gamma) * 255.0).round().min(255.0) as u8;
+        }
+
+        let channels = match channel {
+            Some(channel) => vec![channel.index()],
+            None => vec![0, 1, 2],
+        };
+        for px in self.image.pixels_mut() {
+            for &c in &channels {
+                px.0[c] = table[px.0[c] as usize];
+            }
+        }
+
+        Ok(())
+    }
+
+    fn modulate(&mut self, brightness: u8, saturation: u8, hue: u8) -> Result<(), Box<dyn Error>> {
+        let brightness = brightness as f64 / 100.0;
+        let saturation = saturation as f64 / 100.0;
+        // 0 and 200 both rotate by half a turn, as ImageMagick does
+        let rotation = (hue as f64 - 100.0) / 200.0;
+
+        for px in self.image.pixels_mut() {
+            let [r, g, b, _] = &mut px.0;
+            let (h, s, l) = hsl(*r, *g, *b);
+            let h = (h + rotation).rem_euclid(1.0);
+            let s = (s * saturation).min(1.0);
+            let l = (l * brightness).min(1.0);
+            let [nr, ng, nb] = from_hsl(h, s, l);
+            *r = nr;
+            *g = ng;
+            *b = nb;
+        }
+
+        Ok(())
+    }
+
+    fn contrast(&mut self, contrast: f64) -> Result<(), Box<dyn Error>> {
+        // Same slope as ImageMagick's -brightness-contrast, pivoting at gray
+        let slope = (PI * (contrast / 100.0 + 1.0) / 4.0).tan().max(0.0);
+        self.map_rgb(|v| slope * (v - 0.5) + 0.5);
+        Ok(())
+    }
+
+    fn sigmoidal_contrast(
+        &mut self,
+        sharpen: bool,
+        strength: f64,
+        midpoint: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        if strength <= 0.0 {
+            return Ok(());
+        }
+
+        // Same curve as ImageMagick's -sigmoidal-contrast, scaled so that 0
+        // and 1 stay put, its inverse reducing contrast
+        let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+        let low = sigmoid(-strength * midpoint);
+        let high = sigmoid(strength * (1.0 - midpoint));
+        if sharpen {
+            self.map_rgb(|v| (sigmoid(strength * (v - midpoint)) - low) / (high - low));
+        } else {
+            self.map_rgb(|v| {
+                let y = (v * (high - low) + low).clamp(1e-12, 1.0 - 1e-12);
+                midpoint - (1.0 / y - 1.0).ln() / strength
+            });
+        }
+        Ok(())
+    }
+
+    fn unsharp(&mut self, sigma: f64, amount: f64, threshold: f64) -> Result<(), Box<dyn Error>> {
+        let blurred = imageops::blur(&self.image, sigma as f32);
+        let threshold = threshold * 255.0;
+        for (px, blurred) in self.image.pixels_mut().zip(blurred.pixels()) {
+            for c in 0..3 {
+                let diff = px.0[c] as f64 - blurred.0[c] as f64;
+                if diff.abs() >= threshold {
+                    px.0[c] = (px.0[c] as f64 + amount * diff).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn blur(&mut self, sigma: f64) -> Result<(), Box<dyn Error>> {
+        self.image = imageops::blur(&self.image, sigma as f32);
+        Ok(())
+    }
+
+    fn posterize(&mut self, levels: u8) -> Result<(), Box<dyn Error>> {
+        let steps = (levels.max(2) - 1) as f64;
+        self.map_rgb(|v| (v * steps).round() / steps);
+        Ok(())
+    }
+
+    fn quantize(&mut self, seeds: &[Rgb]) -> Result<(), Box<dyn Error>> {
+        let pixels = self.rgb()?;
+
+        let mut colors = seeds.to_vec();
+        let locked = vec![false; colors.len()];
+        palette::refine(&pixels, &mut colors, &locked, ColorSpace::Rgb, 100);
+
+        let indices = palette::assign(&pixels, &colors, Metric::Rgb);
+        for (px, &i) in self.image.pixels_mut().zip(&indices) {
+            let color = colors[i];
+            px.0[0] = color.r;
+            px.0[1] = color.g;
+            px.0[2] = color.b;
+        }
+
+        Ok(())
+    }
+
+    fn rgb(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self
+            .image
+            .pixels()
+            .flat_map(|px| px.0[..3].to_vec())
+            .collect())
+    }
+
+    fn rgba(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self.image.as_raw().clone())
+    }
+
+    fn encode_png(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (pixels, color_type) = if self.image.pixels().all(|px| px.0[3] == 255) {
+            (self.rgb()?, ColorType::Rgb8)
+        } else {
+            (self.rgba()?, ColorType::Rgba8)
+        };
+
+        // The encoder writes no timestamp, identical renders are identical bytes
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).encode(
+            &pixels,
+            self.image.width(),
+            self.image.height(),
+            color_type,
+        )?;
+        Ok(png)
+    }
+}
+
+impl Native {
+    /// Applies `f` to the color channels, in `[0, 1]`.
+    fn map_rgb(&mut self, f: impl Fn(f64) -> f64) {
+        let mut table = [0u8; 256];
+        for (value, mapped) in table.iter_mut().enumerate() {
+            *mapped = (f(value as f64 / 255.0) * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+
+        for px in self.image.pixels_mut() {
+            for c in &mut px.0[..3] {
+                *c = table[*c as usize];
+            }
+        }
+    }
+}
+
+/// EXIF orientation of the image at `path`, 1 being upright.
+fn orientation(path: &Path) -> u32 {
+    let exif = File::open(path).ok().and_then(|file| {
+        Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+    });
+    exif.and_then(|exif| {
+        exif.get_field(Tag::Orientation, In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+    })
+    .unwrap_or(1)
+}
+
+/// Hue, saturation and lightness of an 8-bit RGB triplet, all in `[0, 1]`.
+fn hsl(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return (0.0, 0.0, l);
+    }
+
+    let s = if l <= 0.5 {
+        delta / (max + min)
+    } else {
+        delta / (2.0 - max - min)
+    };
+    let h = if max == r {
+        (g - b) / delta + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+
+    (h / 6.0, s, l)
+}
+
+fn from_hsl(h: f64, s: f64, l: f64) -> [u8; 3] {
+    let q = if l < 0.5 {
+        l * (1.0 + s)
+    } else {
+        l + s - l * s
+    };
+    let p = 2.0 * l - q;
+
+    let channel = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0)]
+}
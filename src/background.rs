@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::color::Rgb;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// Every pixel close to the key color.
+    ColorKey,
+    /// Only the pixels close to the key color that are connected to the
+    /// image edges, sparing the matching colors inside the subject.
+    FloodFill,
+}
+
+impl Removal {
+    pub const ALL: [Removal; 2] = [Removal::ColorKey, Removal::FloodFill];
+}
+
+impl Default for Removal {
+    fn default() -> Self {
+        Self::FloodFill
+    }
+}
+
+impl fmt::Display for Removal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::ColorKey => "color key",
+                Self::FloodFill => "flood fill",
+            }
+        )
+    }
+}
+
+/// Makes the background of packed 8-bit RGBA pixels transparent, a pixel
+/// being part of it when within `tolerance` percent of the `key` color.
+pub fn remove(
+    pixels: &mut [u8],
+    width: usize,
+    height: usize,
+    removal: Removal,
+    key: Rgb,
+    tolerance: u8,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Percent of the RGB cube diagonal, as ImageMagick's fuzz
+    let max_distance = (tolerance as f64 / 100.0) * (3.0 * 255.0 * 255.0f64).sqrt();
+    let max_squared = max_distance * max_distance;
+    let matches = |pixels: &[u8], p: usize| {
+        let px = &pixels[p * 4..p * 4 + 3];
+        let (dr, dg, db) = (
+            px[0] as f64 - key.r as f64,
+            px[1] as f64 - key.g as f64,
+            px[2] as f64 - key.b as f64,
+        );
+        dr * dr + dg * dg + db * db <= max_squared
+    };
+
+    match removal {
+        Removal::ColorKey => {
+            for p in 0..width * height {
+                if matches(pixels, p) {
+                    pixels[p * 4 + 3] = 0;
+                }
+            }
+        }
+        Removal::FloodFill => {
+            let mut visited = vec![false; width * height];
+            let mut queue = VecDeque::new();
+
+            let edges = (0..width)
+                .flat_map(|x| vec![(x, 0), (x, height.saturating_sub(1))])
+                .chain((0..height).flat_map(|y| vec![(0, y), (width.saturating_sub(1), y)]));
+            for (x, y) in edges {
+                let p = y * width + x;
+                if !visited[p] && matches(pixels, p) {
+                    visited[p] = true;
+                    queue.push_back((x, y));
+                }
+            }
+
+            while let Some((x, y)) = queue.pop_front() {
+                pixels[(y * width + x) * 4 + 3] = 0;
+
+                let neighbors = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for &(nx, ny) in &neighbors {
+                    if nx >= width || ny >= height {
+                        continue;
+                    }
+                    let p = ny * width + nx;
+                    if !visited[p] && matches(pixels, p) {
+                        visited[p] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+        }
+    }
+}
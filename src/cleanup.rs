@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::palette::Indexed;
+
+/// Removes the connected regions of at most `max_size` pixels, giving them
+/// the most common color around them.
+pub fn orphans(result: &mut Indexed, max_size: usize) {
+    let (width, height) = (result.width, result.height);
+    let snapshot = result.indices.clone();
+    let mut visited = vec![false; width * height];
+
+    for start in 0..width * height {
+        if visited[start] || !result.is_opaque(start) {
+            continue;
+        }
+
+        let color = snapshot[start];
+        let mut region = vec![start];
+        let mut around = BTreeMap::new();
+        visited[start] = true;
+
+        let mut next = 0;
+        while next < region.len() {
+            let p = region[next];
+            next += 1;
+            for &n in neighbors4(p, width, height).iter().flatten() {
+                if !result.is_opaque(n) {
+                    continue;
+                }
+                if snapshot[n] != color {
+                    *around.entry(snapshot[n]).or_insert(0) += 1;
+                } else if !visited[n] {
+                    visited[n] = true;
+                    region.push(n);
+                }
+            }
+        }
+
+        if region.len() <= max_size {
+            if let Some(fill) = most_common(&around) {
+                for p in region {
+                    result.indices[p] = fill;
+                }
+            }
+        }
+    }
+}
+
+/// Recolors the corner pixels of staircases, which make 1 pixel wide lines
+/// look doubled, while keeping the lines connected.
+pub fn jaggies(result: &mut Indexed) {
+    let (width, height) = (result.width, result.height);
+    let same = |result: &Indexed, p: Option<usize>, color: usize| match p {
+        Some(p) => result.is_opaque(p) && result.indices[p] == color,
+        None => false,
+    };
+
+    // Updating in place keeps the next corner of a staircase, the pixel the
+    // line goes through once its neighbor is gone
+    for p in 0..width * height {
+        if !result.is_opaque(p) {
+            continue;
+        }
+        let color = result.indices[p];
+
+        // The image edges cannot tell a line from a cut off region
+        let (up, left, right, down) = match neighbors4(p, width, height) {
+            [Some(up), Some(left), Some(right), Some(down)] => (up, left, right, down),
+            _ => continue,
+        };
+        let (x, y) = (p % width, p / width);
+        let diagonal = |dx: isize, dy: isize| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                None
+            } else {
+                Some(ny as usize * width + nx as usize)
+            }
+        };
+
+        // Only an L made of the pixel and two of its neighbors is a corner,
+        // filling the diagonal in between would make it a solid block
+        let diagonal_same = match [
+            same(result, Some(up), color),
+            same(result, Some(left), color),
+            same(result, Some(right), color),
+            same(result, Some(down), color),
+        ] {
+            [true, true, false, false] => same(result, diagonal(-1, -1), color),
+            [true, false, true, false] => same(result, diagonal(1, -1), color),
+            [false, true, false, true] => same(result, diagonal(-1, 1), color),
+            [false, false, true, true] => same(result, diagonal(1, 1), color),
+            _ => continue,
+        };
+        if diagonal_same {
+            continue;
+        }
+
+        let mut around = BTreeMap::new();
+        let mut transparent = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                match diagonal(dx, dy) {
+                    Some(n) if n != p && !result.is_opaque(n) => transparent += 1,
+                    Some(n) if n != p && result.indices[n] != color => {
+                        *around.entry(result.indices[n]).or_insert(0) += 1
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        match (most_common(&around), result.opaque.as_mut()) {
+            (Some(fill), _) if around[&fill] >= transparent => result.indices[p] = fill,
+            (_, Some(opaque)) if transparent > 0 => opaque[p] = false,
+            _ => (),
+        }
+    }
+}
+
+/// Up, left, right and down neighbors of pixel `p`, if within the image.
+fn neighbors4(p: usize, width: usize, height: usize) -> [Option<usize>; 4] {
+    let (x, y) = (p % width, p / width);
+    [
+        if y > 0 { Some(p - width) } else { None },
+        if x > 0 { Some(p - 1) } else { None },
+        if x + 1 < width { Some(p + 1) } else { None },
+        if y + 1 < height {
+            Some(p + width)
+        } else {
+            None
+        },
+    ]
+}
+
+/// Most frequent color, the lowest index winning ties.
+fn most_common(counts: &BTreeMap<usize, usize>) -> Option<usize> {
+    counts
+        .iter()
+        .max_by(|(ia, a), (ib, b)| a.cmp(b).then(ib.cmp(ia)))
+        .map(|(&i, _)| i)
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::constraint::{Depth, Hardware};
+use crate::pattern::Brand;
+use crate::quantize::Quantizer;
+use crate::terminal::Style;
+use crate::tilemap;
+
+pub const USAGE: &str = "\
+Usage: quixelart [OPTIONS] [INPUT]
+
+Opens INPUT in the editor, or renders it straight to a file with --output.
+
+Options:
+  -s, --seed <SEED>      Seed of the color clustering [default: 0]
+  -q, --quantizer <Q>    kmeans, median-cut, octree, wu or kmeans++ [default: kmeans,
+                         kmeans++ without ImageMagick]
+  -o, --output <FILE>    Renders INPUT to FILE as PNG instead of opening the editor,
+                         animations as APNG or, when FILE ends in .gif, as GIF;
+                         as SVG when FILE ends in .svg and as text when it ends in
+                         .txt (ASCII) or .ans (ANSI)
+      --ansi <STYLE>     Writes .ans outputs in truecolor or 256 colors
+                         [default: truecolor]
+  -p, --print <STYLE>    Prints INPUT to the terminal as truecolor, 256 or ascii text
+                         instead of opening the editor
+      --sheet <WxH>      Slices INPUT into WxH cells sharing a palette, writing
+                         their JSON atlas next to FILE
+      --pattern <BRAND>  Snaps the colors to perler, hama, dmc or lego ones, writing
+                         a printable pattern next to FILE
+      --hardware <H>     Snaps the colors to the ones nes, zx or gameboy show and
+                         limits them per area as these systems do
+      --depth <SYSTEM>   Snaps the colors to the RGB grid of genesis (9-bit),
+                         amiga (12-bit) or snes (15-bit)
+      --tiles <N>        Cuts the result into NxN tiles, writing the unique ones as a
+                         tileset next to FILE along with its Tiled map
+      --tile-flips       Also counts flipped tiles as duplicates with --tiles
+      --tile-format <F>  Writes the map of --tiles as tmx (XML) or tmj (JSON)
+                         [default: tmx]
+      --palette-from <IMAGE>
+                         Remaps INPUT to the palette computed for IMAGE
+  -V, --version          Prints the version
+  -h, --help             Prints this help
+";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub help: bool,
+    pub version: bool,
+    pub seed: Option<u64>,
+    pub quantizer: Option<Quantizer>,
+    pub output: Option<PathBuf>,
+    pub print: Option<Style>,
+    /// Style of the `.ans` outputs.
+    pub ansi: Option<Style>,
+    /// Width and height of the sprite sheet cells.
+    pub sheet: Option<(u16, u16)>,
+    pub pattern: Option<Brand>,
+    pub hardware: Option<Hardware>,
+    pub depth: Option<Depth>,
+    /// Side of the tilemap tiles, in result pixels.
+    pub tiles: Option<u8>,
+    pub tile_flips: bool,
+    pub tile_format: Option<tilemap::Format>,
+    /// Image whose palette is reused.
+    pub palette_from: Option<PathBuf>,
+    pub src_path: Option<PathBuf>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        let mut parsed = Self::default();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "-s" | "--seed" => {
+                    let seed = args.next().ok_or("missing value for --seed")?;
+                    parsed.seed = Some(
+                        seed.parse()
+                            .map_err(|_| format!("invalid seed: {}", seed))?,
+                    );
+                }
+                "-q" | "--quantizer" => {
+                    let quantizer = args.next().ok_or("missing value for --quantizer")?;
+                    parsed.quantizer = Some(quantizer.parse()?);
+                }
+                "-o" | "--output" => {
+                    let output = args.next().ok_or("missing value for --output")?;
+                    parsed.output = Some(output.into());
+                }
+                "-p" | "--print" => {
+                    let print = args.next().ok_or("missing value for --print")?;
+                    parsed.print = Some(print.parse()?);
+                }
+                "--ansi" => {
+                    let ansi = args.next().ok_or("missing value for --ansi")?;
+                    parsed.ansi = Some(
+                        ansi.parse()
+                            .ok()
+                            .filter(|style| Style::ANSI.contains(style))
+                            .ok_or_else(|| format!("invalid ANSI style: {}", ansi))?,
+                    );
+                }
+                "--sheet" => {
+                    let sheet = args.next().ok_or("missing value for --sheet")?;
+                    let mut sides = sheet.splitn(2, 'x').map(|side| side.parse().ok());
+                    let size = sides
+                        .next()
+                        .flatten()
+                        .zip(sides.next().flatten())
+                        .filter(|&(width, height)| width > 0 && height > 0)
+                        .ok_or_else(|| format!("invalid cell size: {}", sheet))?;
+                    parsed.sheet = Some(size);
+                }
+                "--pattern" => {
+                    let pattern = args.next().ok_or("missing value for --pattern")?;
+                    parsed.pattern = Some(pattern.parse()?);
+                }
+                "--hardware" => {
+                    let hardware = args.next().ok_or("missing value for --hardware")?;
+                    parsed.hardware = Some(hardware.parse()?);
+                }
+                "--depth" => {
+                    let depth = args.next().ok_or("missing value for --depth")?;
+                    parsed.depth = Some(depth.parse()?);
+                }
+                "--tiles" => {
+                    let tiles = args.next().ok_or("missing value for --tiles")?;
+                    parsed.tiles = Some(
+                        tiles
+                            .parse()
+                            .ok()
+                            .filter(|&size| size > 0)
+                            .ok_or_else(|| format!("invalid tile size: {}", tiles))?,
+                    );
+                }
+                "--tile-flips" => parsed.tile_flips = true,
+                "--tile-format" => {
+                    let tile_format = args.next().ok_or("missing value for --tile-format")?;
+                    parsed.tile_format = Some(tile_format.parse()?);
+                }
+                "--palette-from" => {
+                    let palette_from = args.next().ok_or("missing value for --palette-from")?;
+                    parsed.palette_from = Some(palette_from.into());
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg).into()),
+                _ if parsed.src_path.is_none() => parsed.src_path = Some(arg.into()),
+                _ => return Err(format!("unexpected argument: {}", arg).into()),
+            }
+        }
+
+        if parsed.output.is_some() && parsed.src_path.is_none() {
+            return Err("--output requires an INPUT".into());
+        }
+        if parsed.print.is_some() && parsed.src_path.is_none() {
+            return Err("--print requires an INPUT".into());
+        }
+        if parsed.tiles.is_some() && parsed.output.is_none() {
+            return Err("--tiles requires --output".into());
+        }
+        if parsed.tile_format.is_some() && parsed.tiles.is_none() {
+            return Err("--tile-format requires --tiles".into());
+        }
+        if parsed.palette_from.is_some() && parsed.output.is_none() && parsed.print.is_none() {
+            return Err("--palette-from requires --output or --print".into());
+        }
+
+        Ok(parsed)
+    }
+}
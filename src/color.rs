@@ -0,0 +1,343 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use iced::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn to_color(self) -> Color {
+        Color::from_rgb8(self.r, self.g, self.b)
+    }
+
+    fn to_linear(self) -> [f64; 3] {
+        [
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+        ]
+    }
+
+    fn from_linear([r, g, b]: [f64; 3]) -> Self {
+        Self::new(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    /// Parses `#RRGGBB`, the leading `#` being optional.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim_start_matches('#');
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("invalid color: {}", s))
+        };
+        if hex.len() != 6 {
+            return Err(format!("invalid color: {}", s));
+        }
+        Ok(Self::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f64 {
+    let c = c as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f64) -> u8 {
+    let c = if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Color space in which colors are clustered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Rgb,
+    Lab,
+    Oklab,
+}
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 3] = [ColorSpace::Rgb, ColorSpace::Lab, ColorSpace::Oklab];
+
+    pub fn coords(self, color: Rgb) -> [f64; 3] {
+        match self {
+            Self::Rgb => [color.r as f64, color.g as f64, color.b as f64],
+            Self::Lab => lab_from_linear(color.to_linear()),
+            Self::Oklab => oklab_from_linear(color.to_linear()),
+        }
+    }
+
+    /// Metric matching euclidean distances in this color space.
+    pub fn metric(self) -> Metric {
+        match self {
+            Self::Rgb => Metric::Rgb,
+            Self::Lab => Metric::Cie76,
+            Self::Oklab => Metric::Oklab,
+        }
+    }
+
+    pub fn rgb(self, coords: [f64; 3]) -> Rgb {
+        match self {
+            Self::Rgb => {
+                let [r, g, b] = coords;
+                let c = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+                Rgb::new(c(r), c(g), c(b))
+            }
+            Self::Lab => Rgb::from_linear(lab_to_linear(coords)),
+            Self::Oklab => Rgb::from_linear(oklab_to_linear(coords)),
+        }
+    }
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        Self::Rgb
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Rgb => "RGB",
+                Self::Lab => "CIELAB",
+                Self::Oklab => "OKLab",
+            }
+        )
+    }
+}
+
+/// Distance used to find the closest color of a fixed palette.
+///
+/// Distances are only meant to be compared with others of the same metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Rgb,
+    WeightedRgb,
+    Cie76,
+    Ciede2000,
+    Oklab,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [
+        Metric::Rgb,
+        Metric::WeightedRgb,
+        Metric::Cie76,
+        Metric::Ciede2000,
+        Metric::Oklab,
+    ];
+
+    pub fn distance(self, a: Rgb, b: Rgb) -> f64 {
+        match self {
+            Self::Rgb => squared(ColorSpace::Rgb.coords(a), ColorSpace::Rgb.coords(b)),
+            Self::WeightedRgb => {
+                let r_mean = (a.r as f64 + b.r as f64) / 2.0;
+                let dr = a.r as f64 - b.r as f64;
+                let dg = a.g as f64 - b.g as f64;
+                let db = a.b as f64 - b.b as f64;
+                (2.0 + r_mean / 256.0) * dr * dr
+                    + 4.0 * dg * dg
+                    + (2.0 + (255.0 - r_mean) / 256.0) * db * db
+            }
+            Self::Cie76 => squared(ColorSpace::Lab.coords(a), ColorSpace::Lab.coords(b)),
+            Self::Ciede2000 => ciede2000(ColorSpace::Lab.coords(a), ColorSpace::Lab.coords(b)),
+            Self::Oklab => squared(ColorSpace::Oklab.coords(a), ColorSpace::Oklab.coords(b)),
+        }
+    }
+}
+
+impl Default for Metric {
+    fn default() -> Self {
+        Self::Rgb
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Rgb => "RGB",
+                Self::WeightedRgb => "weighted RGB",
+                Self::Cie76 => "CIE76",
+                Self::Ciede2000 => "CIEDE2000",
+                Self::Oklab => "OKLab",
+            }
+        )
+    }
+}
+
+/// Squared euclidean distance between two coordinates.
+pub fn squared(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+// D65 reference white
+const XN: f64 = 0.950_47;
+const YN: f64 = 1.0;
+const ZN: f64 = 1.088_83;
+
+const EPSILON: f64 = 216.0 / 24389.0;
+const KAPPA: f64 = 24389.0 / 27.0;
+
+fn lab_from_linear([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let x = 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175_0 * b;
+    let z = 0.019_333_9 * r + 0.119_192_0 * g + 0.950_304_1 * b;
+
+    let f = |t: f64| {
+        if t > EPSILON {
+            t.cbrt()
+        } else {
+            (KAPPA * t + 16.0) / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x / XN), f(y / YN), f(z / ZN));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn lab_to_linear([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let f_inv = |f: f64| {
+        let f3 = f * f * f;
+        if f3 > EPSILON {
+            f3
+        } else {
+            (116.0 * f - 16.0) / KAPPA
+        }
+    };
+    let (x, y, z) = (f_inv(fx) * XN, f_inv(fy) * YN, f_inv(fz) * ZN);
+
+    [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266_0 * x + 1.876_010_8 * y + 0.041_556_0 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ]
+}
+
+fn oklab_from_linear([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let l = (0.412_221_470_8 * r + 0.536_332_536_3 * g + 0.051_445_992_9 * b).cbrt();
+    let m = (0.211_903_498_2 * r + 0.680_699_545_1 * g + 0.107_396_956_6 * b).cbrt();
+    let s = (0.088_302_461_9 * r + 0.281_718_837_6 * g + 0.629_978_700_5 * b).cbrt();
+
+    [
+        0.210_454_255_3 * l + 0.793_617_785_0 * m - 0.004_072_046_8 * s,
+        1.977_998_495_1 * l - 2.428_592_205_0 * m + 0.450_593_709_9 * s,
+        0.025_904_037_1 * l + 0.782_771_766_2 * m - 0.808_675_766_0 * s,
+    ]
+}
+
+fn oklab_to_linear([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let l_ = l + 0.396_337_777_4 * a + 0.215_803_757_3 * b;
+    let m_ = l - 0.105_561_345_8 * a - 0.063_854_172_8 * b;
+    let s_ = l - 0.089_484_177_5 * a - 1.291_485_548_0 * b;
+    let (l, m, s) = (l_ * l_ * l_, m_ * m_ * m_, s_ * s_ * s_);
+
+    [
+        4.076_741_662_1 * l - 3.307_711_591_3 * m + 0.230_969_929_2 * s,
+        -1.268_438_004_6 * l + 2.609_757_401_1 * m - 0.341_319_396_5 * s,
+        -0.004_196_086_3 * l - 0.703_418_614_7 * m + 1.707_614_701_0 * s,
+    ]
+}
+
+/// CIEDE2000 color difference between two CIELAB colors.
+fn ciede2000([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+    let c1 = a1.hypot(b1);
+    let c2 = a2.hypot(b2);
+    let c_mean = (c1 + c2) / 2.0;
+    let g = 0.5 * (1.0 - (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt());
+
+    let a1p = (1.0 + g) * a1;
+    let a2p = (1.0 + g) * a2;
+    let c1p = a1p.hypot(b1);
+    let c2p = a2p.hypot(b2);
+
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            let h = b.atan2(a).to_degrees();
+            if h < 0.0 {
+                h + 360.0
+            } else {
+                h
+            }
+        }
+    };
+    let h1p = hue(b1, a1p);
+    let h2p = hue(b2, a2p);
+
+    let dlp = l2 - l1;
+    let dcp = c2p - c1p;
+    let dhp = if c1p * c2p == 0.0 {
+        0.0
+    } else if (h2p - h1p).abs() <= 180.0 {
+        h2p - h1p
+    } else if h2p - h1p > 180.0 {
+        h2p - h1p - 360.0
+    } else {
+        h2p - h1p + 360.0
+    };
+    let dhp = 2.0 * (c1p * c2p).sqrt() * (dhp.to_radians() / 2.0).sin();
+
+    let lp_mean = (l1 + l2) / 2.0;
+    let cp_mean = (c1p + c2p) / 2.0;
+    let hp_mean = if c1p * c2p == 0.0 {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (hp_mean - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * hp_mean).to_radians().cos()
+        + 0.32 * (3.0 * hp_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * hp_mean - 63.0).to_radians().cos();
+    let d_theta = 30.0 * (-((hp_mean - 275.0) / 25.0).powi(2)).exp();
+    let rc = 2.0 * (cp_mean.powi(7) / (cp_mean.powi(7) + 25f64.powi(7))).sqrt();
+    let sl = 1.0 + 0.015 * (lp_mean - 50.0).powi(2) / (20.0 + (lp_mean - 50.0).powi(2)).sqrt();
+    let sc = 1.0 + 0.045 * cp_mean;
+    let sh = 1.0 + 0.015 * cp_mean * t;
+    let rt = -(2.0 * d_theta * PI / 180.0).sin() * rc;
+
+    ((dlp / sl).powi(2) + (dcp / sc).powi(2) + (dhp / sh).powi(2) + rt * (dcp / sc) * (dhp / sh))
+        .sqrt()
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::color::{Metric, Rgb};
+use crate::histogram::luma;
+use crate::palette::{self, Indexed};
+
+/// The greens of the original Game Boy screen, from darkest to lightest.
+const GAME_BOY_SHADES: [Rgb; 4] = [
+    Rgb::new(0x0F, 0x38, 0x0F),
+    Rgb::new(0x30, 0x62, 0x30),
+    Rgb::new(0x8B, 0xAC, 0x0F),
+    Rgb::new(0x9B, 0xBC, 0x0F),
+];
+
+/// The 8 colors of the ZX Spectrum then their bright variants, black having
+/// none.
+const SPECTRUM_PALETTE: [Rgb; 15] = [
+    Rgb::new(0x00, 0x00, 0x00),
+    Rgb::new(0x00, 0x00, 0xD7),
+    Rgb::new(0xD7, 0x00, 0x00),
+    Rgb::new(0xD7, 0x00, 0xD7),
+    Rgb::new(0x00, 0xD7, 0x00),
+    Rgb::new(0x00, 0xD7, 0xD7),
+    Rgb::new(0xD7, 0xD7, 0x00),
+    Rgb::new(0xD7, 0xD7, 0xD7),
+    Rgb::new(0x00, 0x00, 0xFF),
+    Rgb::new(0xFF, 0x00, 0x00),
+    Rgb::new(0xFF, 0x00, 0xFF),
+    Rgb::new(0x00, 0xFF, 0x00),
+    Rgb::new(0x00, 0xFF, 0xFF),
+    Rgb::new(0xFF, 0xFF, 0x00),
+    Rgb::new(0xFF, 0xFF, 0xFF),
+];
+
+/// The distinct colors of the NES master palette, as FCEUX renders them.
+const NES_PALETTE: [Rgb; 52] = [
+    Rgb::new(0x75, 0x75, 0x75),
+    Rgb::new(0x27, 0x1B, 0x8F),
+    Rgb::new(0x00, 0x00, 0xAB),
+    Rgb::new(0x47, 0x00, 0x9F),
+    Rgb::new(0x8F, 0x00, 0x77),
+    Rgb::new(0xAB, 0x00, 0x13),
+    Rgb::new(0xA7, 0x00, 0x00),
+    Rgb::new(0x7F, 0x0B, 0x00),
+    Rgb::new(0x43, 0x2F, 0x00),
+    Rgb::new(0x00, 0x47, 0x00),
+    Rgb::new(0x00, 0x51, 0x00),
+    Rgb::new(0x00, 0x3F, 0x17),
+    Rgb::new(0x1B, 0x3F, 0x5F),
+    Rgb::new(0x00, 0x00, 0x00),
+    Rgb::new(0xBC, 0xBC, 0xBC),
+    Rgb::new(0x00, 0x73, 0xEF),
+    Rgb::new(0x23, 0x3B, 0xEF),
+    Rgb::new(0x83, 0x00, 0xF3),
+    Rgb::new(0xBF, 0x00, 0xBF),
+    Rgb::new(0xE7, 0x00, 0x5B),
+    Rgb::new(0xDB, 0x2B, 0x00),
+    Rgb::new(0xCB, 0x4F, 0x0F),
+    Rgb::new(0x8B, 0x73, 0x00),
+    Rgb::new(0x00, 0x97, 0x00),
+    Rgb::new(0x00, 0xAB, 0x00),
+    Rgb::new(0x00, 0x93, 0x3B),
+    Rgb::new(0x00, 0x83, 0x8B),
+    Rgb::new(0xFF, 0xFF, 0xFF),
+    Rgb::new(0x3F, 0xBF, 0xFF),
+    Rgb::new(0x5F, 0x97, 0xFF),
+    Rgb::new(0xA7, 0x8B, 0xFD),
+    Rgb::new(0xF7, 0x7B, 0xFF),
+    Rgb::new(0xFF, 0x77, 0xB7),
+    Rgb::new(0xFF, 0x77, 0x63),
+    Rgb::new(0xFF, 0x9B, 0x3B),
+    Rgb::new(0xF3, 0xBF, 0x3F),
+    Rgb::new(0x83, 0xD3, 0x13),
+    Rgb::new(0x4F, 0xDF, 0x4B),
+    Rgb::new(0x58, 0xF8, 0x98),
+    Rgb::new(0x00, 0xEB, 0xDB),
+    Rgb::new(0xAB, 0xE7, 0xFF),
+    Rgb::new(0xC7, 0xD7, 0xFF),
+    Rgb::new(0xD7, 0xCB, 0xFF),
+    Rgb::new(0xFF, 0xC7, 0xFF),
+    Rgb::new(0xFF, 0xC7, 0xDB),
+    Rgb::new(0xFF, 0xBF, 0xB3),
+    Rgb::new(0xFF, 0xDB, 0xAB),
+    Rgb::new(0xFF, 0xE7, 0xA3),
+    Rgb::new(0xE3, 0xFF, 0xA3),
+    Rgb::new(0xAB, 0xF3, 0xBF),
+    Rgb::new(0xB3, 0xFF, 0xCF),
+    Rgb::new(0x9F, 0xFF, 0xF3),
+];
+
+/// A retro system limiting how many colors can meet in an area of the
+/// screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hardware {
+    /// 4 colors of the master palette per 16x16 attribute area.
+    ///
+    /// The backdrop color every area shares is not enforced.
+    Nes,
+    /// 2 of the 15 colors, ink and paper, per 8x8 attribute cell.
+    ///
+    /// Mixing a bright color with a normal one in a cell is not prevented.
+    Spectrum,
+    /// 4 shades of green over the whole screen.
+    GameBoy,
+}
+
+impl Hardware {
+    pub const ALL: [Hardware; 3] = [Hardware::Nes, Hardware::Spectrum, Hardware::GameBoy];
+
+    /// Width and height of the areas sharing colors, in result pixels, `None`
+    /// when the whole image is one.
+    pub fn cell(self) -> Option<(usize, usize)> {
+        match self {
+            Self::Nes => Some((16, 16)),
+            Self::Spectrum => Some((8, 8)),
+            Self::GameBoy => None,
+        }
+    }
+
+    /// Most colors an area can hold.
+    pub fn colors(self) -> usize {
+        match self {
+            Self::Nes => 4,
+            Self::Spectrum => 2,
+            Self::GameBoy => 4,
+        }
+    }
+
+    /// The colors the system can show, which the palette is snapped to
+    /// before the areas pick theirs, `None` when they are shades instead.
+    pub fn master_palette(self) -> Option<&'static [Rgb]> {
+        match self {
+            Self::Nes => Some(&NES_PALETTE),
+            Self::Spectrum => Some(&SPECTRUM_PALETTE),
+            Self::GameBoy => None,
+        }
+    }
+
+    /// The only colors the screen shows from darkest to lightest, assigned
+    /// by brightness once the areas picked theirs, `None` when the colors
+    /// come from a master palette.
+    pub fn shades(self) -> Option<&'static [Rgb]> {
+        match self {
+            Self::GameBoy => Some(&GAME_BOY_SHADES),
+            Self::Nes | Self::Spectrum => None,
+        }
+    }
+}
+
+impl Default for Hardware {
+    fn default() -> Self {
+        Self::Nes
+    }
+}
+
+impl fmt::Display for Hardware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Nes => "NES",
+                Self::Spectrum => "ZX Spectrum",
+                Self::GameBoy => "Game Boy",
+            }
+        )
+    }
+}
+
+impl FromStr for Hardware {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nes" => Ok(Self::Nes),
+            "zx" => Ok(Self::Spectrum),
+            "gameboy" => Ok(Self::GameBoy),
+            _ => Err(format!("unknown hardware: {}", s)),
+        }
+    }
+}
+
+/// RGB grid of a retro system, as bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// 9-bit colors of the Genesis, as `0BGR` words with even nibbles.
+    Genesis,
+    /// 12-bit colors of the Amiga OCS, as `RGB` words.
+    Amiga,
+    /// 15-bit colors of the SNES and GBA, as `BGR555` words.
+    Snes,
+}
+
+impl Depth {
+    pub const ALL: [Depth; 3] = [Depth::Genesis, Depth::Amiga, Depth::Snes];
+
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::Genesis => 3,
+            Self::Amiga => 4,
+            Self::Snes => 5,
+        }
+    }
+
+    /// The color of the grid closest to `color`.
+    pub fn snap(self, color: Rgb) -> Rgb {
+        let max = (1 << self.bits()) - 1;
+        let snap = |c: u8| ((self.level(c) * 255 + max / 2) / max) as u8;
+        Rgb::new(snap(color.r), snap(color.g), snap(color.b))
+    }
+
+    /// How the system's palette registers hold `color`, in hexadecimal.
+    pub fn notation(self, color: Rgb) -> String {
+        let (r, g, b) = (
+            self.level(color.r),
+            self.level(color.g),
+            self.level(color.b),
+        );
+        match self {
+            Self::Genesis => format!("${:04X}", b << 9 | g << 5 | r << 1),
+            Self::Amiga => format!("${:03X}", r << 8 | g << 4 | b),
+            Self::Snes => format!("${:04X}", b << 10 | g << 5 | r),
+        }
+    }
+
+    /// Level of the grid closest to the 8-bit channel value `c`.
+    fn level(self, c: u8) -> u32 {
+        let max = (1 << self.bits()) - 1;
+        (c as u32 * max + 127) / 255
+    }
+}
+
+impl Default for Depth {
+    fn default() -> Self {
+        Self::Snes
+    }
+}
+
+impl fmt::Display for Depth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Genesis => "Genesis 9-bit",
+                Self::Amiga => "Amiga 12-bit",
+                Self::Snes => "SNES/GBA 15-bit",
+            }
+        )
+    }
+}
+
+impl FromStr for Depth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "genesis" => Ok(Self::Genesis),
+            "amiga" => Ok(Self::Amiga),
+            "snes" => Ok(Self::Snes),
+            _ => Err(format!("unknown color depth: {}", s)),
+        }
+    }
+}
+
+/// Repaints the areas of `result` holding more colors than `hardware`
+/// allows with their best colors.
+///
+/// The colors of an area are chosen among the ones it uses so that
+/// recoloring its other pixels to the nearest chosen color, per `metric`,
+/// changes them the least. Transparent pixels are left as-is.
+pub fn apply(result: &mut Indexed, palette: &[Rgb], hardware: Hardware, metric: Metric) {
+    let (cell_width, cell_height) = hardware
+        .cell()
+        .unwrap_or((result.width.max(1), result.height.max(1)));
+    let limit = hardware.colors();
+
+    for y in (0..result.height).step_by(cell_height) {
+        for x in (0..result.width).step_by(cell_width) {
+            let pixels = (y..(y + cell_height).min(result.height))
+                .flat_map(|y| (x..(x + cell_width).min(result.width)).map(move |x| (x, y)))
+                .map(|(x, y)| y * result.width + x)
+                .filter(|&p| result.is_opaque(p))
+                .collect::<Vec<_>>();
+
+            let mut counts = vec![0; palette.len()];
+            for &p in &pixels {
+                counts[result.indices[p]] += 1;
+            }
+            let used = (0..palette.len())
+                .filter(|&i| counts[i] > 0)
+                .map(|i| (i, counts[i]))
+                .collect::<Vec<_>>();
+            if used.len() <= limit {
+                continue;
+            }
+
+            let chosen = best(&used, limit, palette, metric);
+            let colors = chosen.iter().map(|&i| palette[i]).collect::<Vec<_>>();
+            for &p in &pixels {
+                let nearest = palette::nearest(&colors, palette[result.indices[p]], metric);
+                result.indices[p] = chosen[nearest];
+            }
+        }
+    }
+}
+
+/// Replaces the colors of `palette` with the shades of matching brightness
+/// rank, so that they stay distinct when there are no more than `shades`.
+///
+/// The darkest color gets the darkest shade and the lightest one the
+/// lightest shade, the others being spread in between.
+pub fn shade(palette: &mut [Rgb], shades: &[Rgb]) {
+    let brightness = |color: Rgb| luma(color.r, color.g, color.b);
+    let mut order = (0..palette.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| brightness(palette[i]));
+
+    let last = shades.len().saturating_sub(1);
+    for (rank, &i) in order.iter().enumerate() {
+        let shade = if order.len() > 1 {
+            (rank * last + (order.len() - 1) / 2) / (order.len() - 1)
+        } else {
+            // A lone color keeps its brightness
+            (0..shades.len())
+                .min_by_key(|&s| {
+                    (brightness(shades[s]) as i32 - brightness(palette[i]) as i32).abs()
+                })
+                .unwrap_or(0)
+        };
+        palette[i] = shades[shade.min(last)];
+    }
+}
+
+/// The `limit` palette indices among the `used` ones, along with their pixel
+/// counts, that the others are closest to overall.
+///
+/// Colors are picked greedily then swapped with the left out ones for as
+/// long as it lowers the error.
+fn best(used: &[(usize, usize)], limit: usize, palette: &[Rgb], metric: Metric) -> Vec<usize> {
+    let distances = used
+        .iter()
+        .map(|&(a, _)| {
+            used.iter()
+                .map(|&(b, _)| metric.distance(palette[a], palette[b]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let error = |chosen: &[usize]| {
+        used.iter()
+            .enumerate()
+            .map(|(u, &(_, count))| {
+                let nearest = chosen
+                    .iter()
+                    .map(|&c| distances[u][c])
+                    .fold(f64::INFINITY, f64::min);
+                count as f64 * nearest
+            })
+            .sum::<f64>()
+    };
+
+    // Positions in `used` of the chosen colors
+    let mut chosen: Vec<usize> = vec![];
+    while chosen.len() < limit {
+        let mut candidates = (0..used.len()).filter(|u| !chosen.contains(u));
+        let first = match candidates.next() {
+            Some(first) => first,
+            None => break,
+        };
+        let mut best = (first, error(&[&chosen[..], &[first]].concat()));
+        for u in candidates {
+            let candidate_error = error(&[&chosen[..], &[u]].concat());
+            if candidate_error < best.1 {
+                best = (u, candidate_error);
+            }
+        }
+        chosen.push(best.0);
+    }
+
+    let mut current = error(&chosen);
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 0..chosen.len() {
+            for u in 0..used.len() {
+                if chosen.contains(&u) {
+                    continue;
+                }
+                let previous = chosen[i];
+                chosen[i] = u;
+                let swapped = error(&chosen);
+                if swapped < current {
+                    current = swapped;
+                    improved = true;
+                } else {
+                    chosen[i] = previous;
+                }
+            }
+        }
+    }
+
+    chosen.into_iter().map(|u| used[u].0).collect()
+}
@@ -0,0 +1,119 @@
+use iced::canvas::{self, Cursor, Frame, Geometry, Path, Stroke};
+use iced::{Color, Point, Rectangle, Size};
+
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub luma: [u32; 256],
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            luma: [0; 256],
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+        }
+    }
+}
+
+impl Histogram {
+    /// Builds the histogram of packed 8-bit RGB pixels.
+    pub fn from_rgb(pixels: &[u8]) -> Self {
+        let mut histogram = Self::default();
+
+        for px in pixels.chunks_exact(3) {
+            let (r, g, b) = (px[0], px[1], px[2]);
+            histogram.red[r as usize] += 1;
+            histogram.green[g as usize] += 1;
+            histogram.blue[b as usize] += 1;
+            histogram.luma[luma(r, g, b) as usize] += 1;
+        }
+
+        histogram
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.luma.iter().all(|&count| count == 0)
+    }
+}
+
+/// Rec. 601 luma of an 8-bit RGB triplet.
+pub fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32).round() as u8
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Channels {
+    Luma,
+    Rgb,
+}
+
+/// Draws a histogram along with optional black and white point markers,
+/// expressed in percent of the full range.
+#[derive(Debug, Clone)]
+pub struct Chart {
+    pub histogram: Histogram,
+    pub channels: Channels,
+    pub markers: Vec<(u8, u8)>,
+}
+
+impl<Message> canvas::Program<Message> for Chart {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+
+        frame.fill_rectangle(
+            Point::ORIGIN,
+            frame.size(),
+            Color::from_rgba(0.5, 0.5, 0.5, 0.15),
+        );
+
+        let series = match self.channels {
+            Channels::Luma => vec![(&self.histogram.luma, Color::from_rgb(0.6, 0.6, 0.6))],
+            Channels::Rgb => vec![
+                (&self.histogram.red, Color::from_rgba(1.0, 0.2, 0.2, 0.5)),
+                (&self.histogram.green, Color::from_rgba(0.2, 1.0, 0.2, 0.5)),
+                (&self.histogram.blue, Color::from_rgba(0.2, 0.4, 1.0, 0.5)),
+            ],
+        };
+
+        let max = series
+            .iter()
+            .flat_map(|(bins, _)| bins.iter())
+            .copied()
+            .max()
+            .unwrap_or(0);
+
+        if max > 0 {
+            let bin_width = frame.width() / 256.0;
+            for (bins, color) in series {
+                for (i, &count) in bins.iter().enumerate() {
+                    let height = frame.height() * count as f32 / max as f32;
+                    frame.fill_rectangle(
+                        Point::new(i as f32 * bin_width, frame.height() - height),
+                        Size::new(bin_width.max(1.0), height),
+                        color,
+                    );
+                }
+            }
+        }
+
+        for &(black, white) in &self.markers {
+            for &percent in &[black, white] {
+                let x = frame.width() * percent as f32 / 100.0;
+                let line = Path::line(Point::new(x, 0.0), Point::new(x, frame.height()));
+                frame.stroke(
+                    &line,
+                    Stroke::default()
+                        .with_color(Color::from_rgb(0.9, 0.6, 0.1))
+                        .with_width(1.0),
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -1,22 +1,59 @@
+mod animation;
+mod backend;
+mod background;
+mod cleanup;
+mod cli;
+mod color;
+mod constraint;
+mod histogram;
+mod outline;
+mod palette;
+mod params;
+mod pattern;
+mod picker;
+mod pipeline;
+mod quantize;
+mod sheet;
+mod stack;
 mod style;
+mod svg;
+mod terminal;
+mod tilemap;
+mod transform;
 
 use std::env;
 use std::error::Error;
 use std::fs;
-use std::io::prelude::*;
-use std::path::PathBuf;
-use std::sync::Once;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use iced::image::Handle as ImageHandle;
-use iced::{button, scrollable, slider};
+use iced::{button, executor, pick_list, scrollable, slider, text_input, time};
 use iced::{
-    Align, Button, Checkbox, Column, Container, Element, Font, HorizontalAlignment, Image, Length,
-    Row, Sandbox, Scrollable, Settings, Slider, Space, Text, VerticalAlignment,
+    Align, Application, Button, Canvas, Checkbox, Column, Command, Container, Element, Font,
+    HorizontalAlignment, Length, PickList, Row, Scrollable, Settings, Slider, Space, Subscription,
+    Text, TextInput, VerticalAlignment,
 };
-use iced_native::widget::image::Data as ImageData;
-use magick_rust::{bindings as magick, magick_wand_genesis, MagickWand};
 
-static INIT_IMAGE_MAGICK: Once = Once::new();
+use crate::animation::Format;
+use crate::background::Removal;
+use crate::cli::Args;
+use crate::color::{ColorSpace, Metric, Rgb};
+use crate::constraint::{Depth, Hardware};
+use crate::histogram::Histogram;
+use crate::outline::{Connectivity, Side};
+use crate::palette::{self, Indexed, Swatch};
+use crate::params::{Channel, Params, PixelAspect};
+use crate::pattern::Brand;
+use crate::picker::{self, Picker};
+use crate::pipeline::Frame;
+use crate::quantize::Quantizer;
+use crate::sheet::{Atlas, Grid, Slicing};
+use crate::stack::{Adjustment, Kind};
+use crate::terminal::Style;
+use crate::tilemap::Tilemap;
+use crate::transform::{Aspect, Crop, Rotation};
 
 const FONT_PIXEL: Font = Font::External {
     name: "Pixel",
@@ -38,26 +75,99 @@ struct Easel {
     layout: Layout,
     src_button: button::State,
     src_path: Option<PathBuf>,
+    /// Every image selected along with the source, sharing its palette.
+    batch: Vec<PathBuf>,
+    batch_button: button::State,
+    /// Palette shared by the batch, before the stages after `Quantize`
+    /// recolor it, along with the palette key of the settings and the locked
+    /// colors it was computed with.
+    batch_palette: Option<(Params, Vec<Rgb>, Vec<Rgb>)>,
     layout_button: button::State,
     theme_button: button::State,
     img_handle: ImageHandle,
+    img_bytes: Vec<u8>,
+    picker: picker::State,
     scroll: scrollable::State,
+    params: Params,
+    rotation_list: pick_list::State<Rotation>,
+    crop_aspect_list: pick_list::State<Aspect>,
+    crop_edit_button: button::State,
+    crop_editing: bool,
+    crop_handle: ImageHandle,
     pixelize_slider: slider::State,
-    pixelize: u8,
+    pixel_aspect_list: pick_list::State<PixelAspect>,
+    pixel_aspect_width_slider: slider::State,
+    pixel_aspect_height_slider: slider::State,
+    grid_x_slider: slider::State,
+    grid_y_slider: slider::State,
+    sheet_slicing_list: pick_list::State<Slicing>,
+    sheet_width_slider: slider::State,
+    sheet_height_slider: slider::State,
+    sheet_padding_slider: slider::State,
+    sheet_atlas: Atlas,
+    sheet_atlas_list: pick_list::State<Atlas>,
     kcolors_slider: slider::State,
-    kcolors: u8,
-    level_toggle: bool,
-    level_black_slider: slider::State,
-    level_black: u8,
-    level_white_slider: slider::State,
-    level_white: u8,
-    modulate_toggle: bool,
-    modulate_brightness_slider: slider::State,
-    modulate_brightness: u8,
-    modulate_saturation_slider: slider::State,
-    modulate_saturation: u8,
-    modulate_hue_slider: slider::State,
-    modulate_hue: u8,
+    quantizer_list: pick_list::State<Quantizer>,
+    cluster_space_list: pick_list::State<ColorSpace>,
+    remap_metric_list: pick_list::State<Metric>,
+    seed_input: text_input::State,
+    seed_value: String,
+    seed_button: button::State,
+    stage_widgets: Vec<StageWidgets>,
+    stage_histograms: Vec<Histogram>,
+    stage_add_list: pick_list::State<Kind>,
+    alpha_threshold_slider: slider::State,
+    alpha_checkerboard: bool,
+    background_removal_list: pick_list::State<Removal>,
+    background_tolerance_slider: slider::State,
+    background_pick_button: button::State,
+    background_picking: bool,
+    cleanup_orphans_slider: slider::State,
+    cleanup_intensity_slider: slider::State,
+    outline_side_list: pick_list::State<Side>,
+    outline_connectivity_list: pick_list::State<Connectivity>,
+    outline_color_input: text_input::State,
+    pattern_brand_list: pick_list::State<Brand>,
+    constraint_hardware_list: pick_list::State<Hardware>,
+    depth_system_list: pick_list::State<Depth>,
+    outline_color_value: String,
+    tilemap_toggle: bool,
+    /// Side of the tiles, in result pixels.
+    tilemap_size: u8,
+    tilemap_size_slider: slider::State,
+    tilemap_flips: bool,
+    tilemap_format: tilemap::Format,
+    tilemap_format_list: pick_list::State<tilemap::Format>,
+    /// Number of unique tiles in the first result.
+    tilemap_tiles: usize,
+    palette_entries: Vec<PaletteEntry>,
+    palette_selected: Option<usize>,
+    palette_merging: bool,
+    palette_lock_button: button::State,
+    palette_merge_button: button::State,
+    palette_reuse_button: button::State,
+    palette_red_slider: slider::State,
+    palette_green_slider: slider::State,
+    palette_blue_slider: slider::State,
+    results: Vec<Indexed>,
+    delays: Vec<Duration>,
+    frame_handles: Vec<ImageHandle>,
+    frame_index: usize,
+    animation_playing: bool,
+    animation_button: button::State,
+    export_format: Format,
+    export_format_list: pick_list::State<Format>,
+    /// Style of the `.ans` exports.
+    ansi_style: Style,
+    ansi_style_list: pick_list::State<Style>,
+    sheet: Option<Grid>,
+    frame: Frame,
+    quantize_time: Duration,
+    quantize_error: f64,
+    analysis_toggle: bool,
+    source_histogram: Histogram,
+    result_histogram: Histogram,
+    result_swatches: Vec<Swatch>,
     save_button: button::State,
     save_as_button: button::State,
     save_path: Option<PathBuf>,
@@ -68,24 +178,114 @@ struct Easel {
 #[derive(Debug, Clone)]
 enum Event {
     SourcePressed,
+    BatchNextPressed,
+    RotationSelected(Rotation),
+    FlipHorizontalToggled(bool),
+    FlipVerticalToggled(bool),
+    CropToggled(bool),
+    CropAspectSelected(Aspect),
+    CropEditPressed,
+    PreviewDragged((f32, f32), (f32, f32)),
     LayoutPressed,
     ThemePressed,
     SliderPixelizeChanged(u8),
     SliderPixelizeReleased,
+    PixelAspectSelected(PixelAspect),
+    SliderPixelAspectWidthChanged(u8),
+    SliderPixelAspectHeightChanged(u8),
+    SliderPixelAspectReleased,
+    SheetToggled(bool),
+    SheetSlicingSelected(Slicing),
+    SliderSheetCellWidthChanged(u16),
+    SliderSheetCellHeightChanged(u16),
+    SliderSheetColumnsChanged(u8),
+    SliderSheetRowsChanged(u8),
+    SliderSheetCellsReleased,
+    SliderSheetPaddingChanged(u8),
+    SliderSheetPaddingReleased,
+    SheetAtlasSelected(Atlas),
+    SliderGridXChanged(u16),
+    SliderGridYChanged(u16),
     SliderKcolorsChanged(u8),
     SliderKcolorsReleased,
-    LevelToggled(bool),
-    SliderLevelBlackChanged(u8),
-    SliderLevelBlackReleased,
-    SliderLevelWhiteChanged(u8),
-    SliderLevelWhiteReleased,
-    ModulateToggled(bool),
-    SliderModulateBrightnessChanged(u8),
-    SliderModulateBrightnessReleased,
-    SliderModulateSaturationChanged(u8),
-    SliderModulateSaturationReleased,
-    SliderModulateHueChanged(u8),
-    SliderModulateHueReleased,
+    SeedChanged(String),
+    SeedSubmitted,
+    SeedPressed,
+    QuantizerSelected(Quantizer),
+    ClusterSpaceSelected(ColorSpace),
+    RemapMetricSelected(Metric),
+    StageAddSelected(Kind),
+    StageToggled(usize, bool),
+    StageUpPressed(usize),
+    StageDownPressed(usize),
+    StageDuplicatePressed(usize),
+    StageRemovePressed(usize),
+    SliderStageReleased,
+    LevelPerChannelToggled(usize, bool),
+    SliderLevelBlackChanged(usize, u8),
+    SliderLevelWhiteChanged(usize, u8),
+    SliderLevelGammaChanged(usize, u16),
+    SliderLevelChannelBlackChanged(usize, Channel, u8),
+    SliderLevelChannelWhiteChanged(usize, Channel, u8),
+    SliderLevelChannelGammaChanged(usize, Channel, u16),
+    SliderModulateBrightnessChanged(usize, u8),
+    SliderModulateSaturationChanged(usize, u8),
+    SliderModulateHueChanged(usize, u8),
+    ContrastSigmoidalToggled(usize, bool),
+    SliderContrastAmountChanged(usize, u8),
+    SliderContrastMidpointChanged(usize, u8),
+    SliderUnsharpSigmaChanged(usize, u8),
+    SliderUnsharpAmountChanged(usize, u8),
+    SliderUnsharpThresholdChanged(usize, u8),
+    SliderBlurSigmaChanged(usize, u8),
+    SliderPosterizeLevelsChanged(usize, u8),
+    AlphaToggled(bool),
+    SliderAlphaThresholdChanged(u8),
+    SliderAlphaThresholdReleased,
+    AlphaExcludeToggled(bool),
+    AlphaCheckerboardToggled(bool),
+    BackgroundToggled(bool),
+    BackgroundRemovalSelected(Removal),
+    SliderBackgroundToleranceChanged(u8),
+    SliderBackgroundToleranceReleased,
+    BackgroundPickPressed,
+    PreviewPicked(f32, f32),
+    CleanupToggled(bool),
+    SliderCleanupOrphansChanged(u8),
+    SliderCleanupOrphansReleased,
+    CleanupJaggiesToggled(bool),
+    SliderCleanupIntensityChanged(u8),
+    SliderCleanupIntensityReleased,
+    OutlineToggled(bool),
+    OutlineSideSelected(Side),
+    OutlineConnectivitySelected(Connectivity),
+    OutlineAutoToggled(bool),
+    OutlineColorChanged(String),
+    OutlineColorSubmitted,
+    PatternToggled(bool),
+    PatternBrandSelected(Brand),
+    ConstraintToggled(bool),
+    ConstraintHardwareSelected(Hardware),
+    DepthToggled(bool),
+    DepthSystemSelected(Depth),
+    TilemapToggled(bool),
+    SliderTilemapSizeChanged(u8),
+    SliderTilemapSizeReleased,
+    TilemapFlipsToggled(bool),
+    TilemapFormatSelected(tilemap::Format),
+    PaletteSwatchPressed(usize),
+    PaletteLockPressed,
+    PaletteMergePressed,
+    PaletteReusePressed,
+    SliderPaletteRedChanged(u8),
+    SliderPaletteGreenChanged(u8),
+    SliderPaletteBlueChanged(u8),
+    SliderPaletteColorReleased,
+    AnalysisToggled(bool),
+    AnimationPlayPressed,
+    AnimationTicked(Instant),
+    ExportFormatSelected(Format),
+    AnsiStyleSelected(Style),
     SavePressed,
     SaveAsPressed,
 }
@@ -105,41 +305,167 @@ impl Layout {
     }
 }
 
-impl Sandbox for Easel {
-    type Message = Event;
+/// Widgets of a stage of the adjustment stack, the sliders being used in
+/// order by the stage's settings.
+#[derive(Default)]
+struct StageWidgets {
+    sliders: [slider::State; 3],
+    channel_sliders: [ChannelLevelSliders; 3],
+    up_button: button::State,
+    down_button: button::State,
+    duplicate_button: button::State,
+    remove_button: button::State,
+}
+
+/// Sliders of the levels applied to a single channel.
+#[derive(Default)]
+struct ChannelLevelSliders {
+    black: slider::State,
+    white: slider::State,
+    gamma: slider::State,
+}
 
-    fn new() -> Self {
+/// A color of the quantized result, editable by the user.
+///
+/// Locked entries are kept as-is when the image is re-rendered, the other
+/// palette colors being re-optimized around them.
+struct PaletteEntry {
+    color: Rgb,
+    locked: bool,
+    button: button::State,
+}
+
+impl PaletteEntry {
+    fn new(color: Rgb, locked: bool) -> Self {
         Self {
+            color,
+            locked,
+            button: button::State::new(),
+        }
+    }
+}
+
+/// Startup state of the editor.
+#[derive(Debug, Default)]
+struct Flags {
+    params: Params,
+    src_path: Option<PathBuf>,
+}
+
+impl Application for Easel {
+    type Executor = executor::Default;
+    type Message = Event;
+    type Flags = Flags;
+
+    fn new(flags: Flags) -> (Self, Command<Event>) {
+        let save_path = flags
+            .src_path
+            .as_ref()
+            .and_then(|p| p.parent())
+            .map(PathBuf::from);
+
+        let mut easel = Self {
             theme: style::Theme::Dark,
             layout: Layout::Columns,
             src_button: button::State::new(),
-            src_path: None,
+            src_path: flags.src_path,
+            batch: vec![],
+            batch_button: button::State::new(),
+            batch_palette: None,
             layout_button: button::State::new(),
             theme_button: button::State::new(),
             img_handle: ImageHandle::from_memory(vec![]),
+            img_bytes: vec![],
+            picker: picker::State::new(),
             scroll: scrollable::State::new(),
+            seed_value: flags.params.seed.to_string(),
+            params: flags.params,
+            rotation_list: pick_list::State::default(),
+            crop_aspect_list: pick_list::State::default(),
+            crop_edit_button: button::State::new(),
+            crop_editing: false,
+            crop_handle: ImageHandle::from_memory(vec![]),
             pixelize_slider: slider::State::new(),
-            pixelize: 80,
+            pixel_aspect_list: pick_list::State::default(),
+            pixel_aspect_width_slider: slider::State::new(),
+            pixel_aspect_height_slider: slider::State::new(),
+            grid_x_slider: slider::State::new(),
+            grid_y_slider: slider::State::new(),
+            sheet_slicing_list: pick_list::State::default(),
+            sheet_width_slider: slider::State::new(),
+            sheet_height_slider: slider::State::new(),
+            sheet_padding_slider: slider::State::new(),
+            sheet_atlas: Atlas::default(),
+            sheet_atlas_list: pick_list::State::default(),
             kcolors_slider: slider::State::new(),
-            kcolors: 32,
-            level_toggle: true,
-            level_black_slider: slider::State::new(),
-            level_black: 10,
-            level_white_slider: slider::State::new(),
-            level_white: 80,
-            modulate_toggle: false,
-            modulate_brightness_slider: slider::State::new(),
-            modulate_brightness: 100,
-            modulate_saturation_slider: slider::State::new(),
-            modulate_saturation: 100,
-            modulate_hue_slider: slider::State::new(),
-            modulate_hue: 100,
+            quantizer_list: pick_list::State::default(),
+            cluster_space_list: pick_list::State::default(),
+            remap_metric_list: pick_list::State::default(),
+            seed_input: text_input::State::new(),
+            seed_button: button::State::new(),
+            stage_widgets: vec![],
+            stage_histograms: vec![],
+            stage_add_list: pick_list::State::default(),
+            alpha_threshold_slider: slider::State::new(),
+            alpha_checkerboard: true,
+            background_removal_list: pick_list::State::default(),
+            background_tolerance_slider: slider::State::new(),
+            background_pick_button: button::State::new(),
+            background_picking: false,
+            cleanup_orphans_slider: slider::State::new(),
+            cleanup_intensity_slider: slider::State::new(),
+            outline_side_list: pick_list::State::default(),
+            outline_connectivity_list: pick_list::State::default(),
+            outline_color_input: text_input::State::new(),
+            pattern_brand_list: pick_list::State::default(),
+            constraint_hardware_list: pick_list::State::default(),
+            depth_system_list: pick_list::State::default(),
+            outline_color_value: flags.params.outline_color.to_string(),
+            tilemap_toggle: false,
+            tilemap_size: 16,
+            tilemap_size_slider: slider::State::new(),
+            tilemap_flips: false,
+            tilemap_format: tilemap::Format::default(),
+            tilemap_format_list: pick_list::State::default(),
+            tilemap_tiles: 0,
+            palette_entries: vec![],
+            palette_selected: None,
+            palette_merging: false,
+            palette_lock_button: button::State::new(),
+            palette_merge_button: button::State::new(),
+            palette_reuse_button: button::State::new(),
+            palette_red_slider: slider::State::new(),
+            palette_green_slider: slider::State::new(),
+            palette_blue_slider: slider::State::new(),
+            results: vec![],
+            delays: vec![],
+            frame_handles: vec![],
+            frame_index: 0,
+            animation_playing: true,
+            animation_button: button::State::new(),
+            export_format: Format::default(),
+            export_format_list: pick_list::State::default(),
+            ansi_style: Style::default(),
+            ansi_style_list: pick_list::State::default(),
+            sheet: None,
+            frame: Frame::default(),
+            quantize_time: Duration::default(),
+            quantize_error: 0.0,
+            analysis_toggle: false,
+            source_histogram: Histogram::default(),
+            result_histogram: Histogram::default(),
+            result_swatches: vec![],
             save_button: button::State::new(),
             save_as_button: button::State::new(),
-            save_path: None,
+            save_path,
             save_file: None,
             saved: false,
-        }
+        };
+
+        easel.sync_stage_widgets();
+        easel.make_img();
+
+        (easel, Command::none())
     }
 
     fn title(&self) -> String {
@@ -152,7 +478,7 @@ impl Sandbox for Easel {
         }
     }
 
-    fn update(&mut self, evt: Event) {
+    fn update(&mut self, evt: Event) -> Command<Event> {
         match evt {
             Event::LayoutPressed => {
                 self.layout.swap();
@@ -161,11 +487,19 @@ impl Sandbox for Easel {
                 self.theme.swap();
             }
             Event::SourcePressed => {
-                let file_path = rfd::FileDialog::new()
+                let file_paths = rfd::FileDialog::new()
                     // .add_filter("rust", &["rs", "toml"])
                     // .set_directory(&path)
-                    .pick_file();
+                    .pick_files()
+                    .unwrap_or_default();
+                let file_path = file_paths.first().cloned();
                 self.src_path = file_path.clone();
+                self.batch = if file_paths.len() > 1 {
+                    file_paths
+                } else {
+                    vec![]
+                };
+                self.batch_palette = None;
 
                 if let Some(mut file_path) = file_path {
                     file_path.pop();
@@ -173,52 +507,530 @@ impl Sandbox for Easel {
                     self.save_file = None;
                 }
 
+                self.palette_entries.clear();
+                self.make_img();
+                self.make_crop_preview();
+            }
+            Event::BatchNextPressed => {
+                let current = self
+                    .batch
+                    .iter()
+                    .position(|path| Some(path) == self.src_path.as_ref());
+                if let Some(current) = current {
+                    self.src_path = Some(self.batch[(current + 1) % self.batch.len()].clone());
+                    self.make_img();
+                    self.make_crop_preview();
+                }
+            }
+            Event::RotationSelected(rotation) => {
+                self.params.rotation = rotation;
+                self.make_img();
+                self.make_crop_preview();
+            }
+            Event::FlipHorizontalToggled(flip_horizontal) => {
+                self.params.flip_horizontal = flip_horizontal;
+                self.make_img();
+                self.make_crop_preview();
+            }
+            Event::FlipVerticalToggled(flip_vertical) => {
+                self.params.flip_vertical = flip_vertical;
                 self.make_img();
+                self.make_crop_preview();
+            }
+            Event::CropToggled(crop_toggle) => {
+                self.params.crop_toggle = crop_toggle;
+                if !crop_toggle {
+                    self.crop_editing = false;
+                }
+                self.make_img();
+            }
+            Event::CropAspectSelected(crop_aspect) => {
+                self.params.crop_aspect = crop_aspect;
+                if self.params.crop_toggle {
+                    self.make_img();
+                }
+                self.make_crop_preview();
+            }
+            Event::CropEditPressed => {
+                self.crop_editing = !self.crop_editing;
+                self.background_picking = false;
+                self.make_crop_preview();
+            }
+            Event::PreviewDragged(start, end) => {
+                let crop = Crop::from_corners(start, end);
+                // A click without dragging resets the crop
+                self.params.crop = if crop.width > 0.0 && crop.height > 0.0 {
+                    crop
+                } else {
+                    Crop::default()
+                };
+                self.make_img();
+                self.make_crop_preview();
             }
             Event::SliderPixelizeChanged(pixelize) => {
-                self.pixelize = pixelize;
+                self.params.pixelize = pixelize;
+            }
+            Event::PixelAspectSelected(pixel_aspect) => {
+                self.params.pixel_aspect = pixel_aspect;
+                self.make_img();
+            }
+            Event::SliderPixelAspectWidthChanged(width) => {
+                self.params.pixel_aspect_custom.0 = width;
+            }
+            Event::SliderPixelAspectHeightChanged(height) => {
+                self.params.pixel_aspect_custom.1 = height;
+            }
+            Event::SliderPixelAspectReleased => {
+                self.make_img();
+            }
+            Event::SheetToggled(sheet_toggle) => {
+                self.params.sheet_toggle = sheet_toggle;
+                self.make_img();
+            }
+            Event::SheetSlicingSelected(sheet_slicing) => {
+                self.params.sheet_slicing = sheet_slicing;
+                if self.params.sheet_toggle {
+                    self.make_img();
+                }
+            }
+            Event::SliderSheetCellWidthChanged(width) => {
+                self.params.sheet_cell_size.0 = width;
+            }
+            Event::SliderSheetCellHeightChanged(height) => {
+                self.params.sheet_cell_size.1 = height;
+            }
+            Event::SliderSheetColumnsChanged(columns) => {
+                self.params.sheet_cell_count.0 = columns;
+            }
+            Event::SliderSheetRowsChanged(rows) => {
+                self.params.sheet_cell_count.1 = rows;
+            }
+            Event::SliderSheetCellsReleased => {
+                if self.params.sheet_toggle {
+                    self.make_img();
+                }
+            }
+            Event::SliderSheetPaddingChanged(sheet_padding) => {
+                self.params.sheet_padding = sheet_padding;
+            }
+            Event::SliderSheetPaddingReleased => {
+                // The padding only matters when reassembling the cells
+                if self.sheet.is_some() {
+                    self.render_result();
+                }
+            }
+            Event::SheetAtlasSelected(sheet_atlas) => {
+                self.sheet_atlas = sheet_atlas;
+            }
+            Event::SliderGridXChanged(grid_x) => {
+                if grid_x != self.params.grid_x {
+                    self.params.grid_x = grid_x;
+                    self.make_img();
+                }
+            }
+            Event::SliderGridYChanged(grid_y) => {
+                if grid_y != self.params.grid_y {
+                    self.params.grid_y = grid_y;
+                    self.make_img();
+                }
             }
             Event::SliderKcolorsChanged(kcolors) => {
-                self.kcolors = kcolors;
+                self.params.kcolors = kcolors;
             }
             Event::SliderPixelizeReleased | Event::SliderKcolorsReleased => {
                 self.make_img();
             }
-            Event::LevelToggled(level_toggle) => {
-                self.level_toggle = level_toggle;
+            Event::SeedChanged(seed_value) => {
+                if seed_value.chars().all(|c| c.is_ascii_digit()) {
+                    self.seed_value = seed_value;
+                }
+            }
+            Event::SeedSubmitted => {
+                if let Ok(seed) = self.seed_value.parse() {
+                    self.params.seed = seed;
+                    self.make_img();
+                }
+            }
+            Event::SeedPressed => {
+                let nanos = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.subsec_nanos())
+                    .unwrap_or_default();
+                self.params.seed = quantize::Rng::new(nanos as u64).next_u64();
+                self.seed_value = self.params.seed.to_string();
                 self.make_img();
             }
-            Event::SliderLevelBlackChanged(level_black) => {
-                self.level_black = level_black;
+            Event::QuantizerSelected(quantizer) => {
+                self.params.quantizer = quantizer;
+                self.make_img();
             }
-            Event::SliderLevelWhiteChanged(level_white) => {
-                self.level_white = level_white;
+            Event::ClusterSpaceSelected(cluster_space) => {
+                self.params.cluster_space = cluster_space;
+                self.make_img();
             }
-            Event::SliderLevelBlackReleased | Event::SliderLevelWhiteReleased => {
-                if self.level_toggle {
+            Event::RemapMetricSelected(remap_metric) => {
+                self.params.remap_metric = remap_metric;
+                self.make_img();
+            }
+            Event::StageAddSelected(kind) => {
+                stack::insert(&mut self.params.stack, kind);
+                self.sync_stage_widgets();
+                self.make_img();
+            }
+            Event::StageToggled(i, enabled) => {
+                self.params.stack[i].enabled = enabled;
+                self.make_img();
+            }
+            Event::StageUpPressed(i) => {
+                if i > 0 && stack::move_down(&mut self.params.stack, i - 1) {
+                    self.make_img();
+                }
+            }
+            Event::StageDownPressed(i) => {
+                if stack::move_down(&mut self.params.stack, i) {
                     self.make_img();
                 }
             }
-            Event::ModulateToggled(modulate_toggle) => {
-                self.modulate_toggle = modulate_toggle;
+            Event::StageDuplicatePressed(i) => {
+                let stage = self.params.stack[i].clone();
+                self.params.stack.insert(i + 1, stage);
+                self.sync_stage_widgets();
                 self.make_img();
             }
-            Event::SliderModulateBrightnessChanged(modulate_brightness) => {
-                self.modulate_brightness = modulate_brightness;
+            Event::StageRemovePressed(i) => {
+                self.params.stack.remove(i);
+                self.sync_stage_widgets();
+                self.make_img();
             }
-            Event::SliderModulateSaturationChanged(modulate_saturation) => {
-                self.modulate_saturation = modulate_saturation;
+            Event::SliderStageReleased => {
+                self.make_img();
+            }
+            Event::LevelPerChannelToggled(i, level_per_channel) => {
+                if let Some(Adjustment::Levels { per_channel, .. }) = self.adjustment_mut(i) {
+                    *per_channel = level_per_channel;
+                }
+                self.make_img();
             }
-            Event::SliderModulateHueChanged(modulate_hue) => {
-                self.modulate_hue = modulate_hue;
+            Event::SliderLevelBlackChanged(i, black) => {
+                if let Some(Adjustment::Levels { levels, .. }) = self.adjustment_mut(i) {
+                    levels.black = black;
+                }
+            }
+            Event::SliderLevelWhiteChanged(i, white) => {
+                if let Some(Adjustment::Levels { levels, .. }) = self.adjustment_mut(i) {
+                    levels.white = white;
+                }
+            }
+            Event::SliderLevelGammaChanged(i, gamma) => {
+                if let Some(Adjustment::Levels { levels, .. }) = self.adjustment_mut(i) {
+                    levels.gamma = gamma;
+                }
+            }
+            Event::SliderLevelChannelBlackChanged(i, channel, black) => {
+                if let Some(Adjustment::Levels { channels, .. }) = self.adjustment_mut(i) {
+                    channels[channel.index()].black = black;
+                }
+            }
+            Event::SliderLevelChannelWhiteChanged(i, channel, white) => {
+                if let Some(Adjustment::Levels { channels, .. }) = self.adjustment_mut(i) {
+                    channels[channel.index()].white = white;
+                }
+            }
+            Event::SliderLevelChannelGammaChanged(i, channel, gamma) => {
+                if let Some(Adjustment::Levels { channels, .. }) = self.adjustment_mut(i) {
+                    channels[channel.index()].gamma = gamma;
+                }
+            }
+            Event::SliderModulateBrightnessChanged(i, modulate_brightness) => {
+                if let Some(Adjustment::Modulate { brightness, .. }) = self.adjustment_mut(i) {
+                    *brightness = modulate_brightness;
+                }
+            }
+            Event::SliderModulateSaturationChanged(i, modulate_saturation) => {
+                if let Some(Adjustment::Modulate { saturation, .. }) = self.adjustment_mut(i) {
+                    *saturation = modulate_saturation;
+                }
+            }
+            Event::SliderModulateHueChanged(i, modulate_hue) => {
+                if let Some(Adjustment::Modulate { hue, .. }) = self.adjustment_mut(i) {
+                    *hue = modulate_hue;
+                }
+            }
+            Event::ContrastSigmoidalToggled(i, contrast_sigmoidal) => {
+                if let Some(Adjustment::Contrast { sigmoidal, .. }) = self.adjustment_mut(i) {
+                    *sigmoidal = contrast_sigmoidal;
+                }
+                self.make_img();
+            }
+            Event::SliderContrastAmountChanged(i, contrast_amount) => {
+                if let Some(Adjustment::Contrast { amount, .. }) = self.adjustment_mut(i) {
+                    *amount = contrast_amount;
+                }
+            }
+            Event::SliderContrastMidpointChanged(i, contrast_midpoint) => {
+                if let Some(Adjustment::Contrast { midpoint, .. }) = self.adjustment_mut(i) {
+                    *midpoint = contrast_midpoint;
+                }
+            }
+            Event::SliderUnsharpSigmaChanged(i, unsharp_sigma) => {
+                if let Some(Adjustment::Unsharp { sigma, .. }) = self.adjustment_mut(i) {
+                    *sigma = unsharp_sigma;
+                }
+            }
+            Event::SliderUnsharpAmountChanged(i, unsharp_amount) => {
+                if let Some(Adjustment::Unsharp { amount, .. }) = self.adjustment_mut(i) {
+                    *amount = unsharp_amount;
+                }
             }
-            Event::SliderModulateBrightnessReleased
-            | Event::SliderModulateSaturationReleased
-            | Event::SliderModulateHueReleased => {
-                if self.modulate_toggle {
+            Event::SliderUnsharpThresholdChanged(i, unsharp_threshold) => {
+                if let Some(Adjustment::Unsharp { threshold, .. }) = self.adjustment_mut(i) {
+                    *threshold = unsharp_threshold;
+                }
+            }
+            Event::SliderBlurSigmaChanged(i, blur_sigma) => {
+                if let Some(Adjustment::Blur { sigma }) = self.adjustment_mut(i) {
+                    *sigma = blur_sigma;
+                }
+            }
+            Event::SliderPosterizeLevelsChanged(i, posterize_levels) => {
+                if let Some(Adjustment::Posterize { levels }) = self.adjustment_mut(i) {
+                    *levels = posterize_levels;
+                }
+            }
+            Event::AlphaToggled(alpha_toggle) => {
+                self.params.alpha_toggle = alpha_toggle;
+                self.make_img();
+            }
+            Event::SliderAlphaThresholdChanged(alpha_threshold) => {
+                self.params.alpha_threshold = alpha_threshold;
+            }
+            Event::SliderAlphaThresholdReleased => {
+                if self.params.alpha_toggle {
+                    self.make_img();
+                }
+            }
+            Event::AlphaExcludeToggled(alpha_exclude) => {
+                self.params.alpha_exclude = alpha_exclude;
+                if self.params.alpha_toggle {
+                    self.make_img();
+                }
+            }
+            Event::AlphaCheckerboardToggled(alpha_checkerboard) => {
+                self.alpha_checkerboard = alpha_checkerboard;
+                self.render_result();
+            }
+            Event::BackgroundToggled(background_toggle) => {
+                self.params.background_toggle = background_toggle;
+                self.background_picking = false;
+                self.make_img();
+            }
+            Event::BackgroundRemovalSelected(background_removal) => {
+                self.params.background_removal = background_removal;
+                if self.params.background_toggle {
+                    self.make_img();
+                }
+            }
+            Event::SliderBackgroundToleranceChanged(background_tolerance) => {
+                self.params.background_tolerance = background_tolerance;
+            }
+            Event::SliderBackgroundToleranceReleased => {
+                if self.params.background_toggle {
+                    self.make_img();
+                }
+            }
+            Event::BackgroundPickPressed => {
+                self.background_picking = !self.background_picking;
+            }
+            Event::PreviewPicked(x, y) => {
+                self.background_picking = false;
+                let picked = self
+                    .src_path
+                    .as_ref()
+                    .and_then(|src_path| pipeline::pick(src_path, &self.params, (x, y)).ok());
+                if let Some(background_key) = picked {
+                    self.params.background_key = background_key;
+                    if self.params.background_toggle {
+                        self.make_img();
+                    }
+                }
+            }
+            Event::CleanupToggled(cleanup_toggle) => {
+                self.params.cleanup_toggle = cleanup_toggle;
+                self.make_img();
+            }
+            Event::SliderCleanupOrphansChanged(cleanup_orphans) => {
+                self.params.cleanup_orphans = cleanup_orphans;
+            }
+            Event::CleanupJaggiesToggled(cleanup_jaggies) => {
+                self.params.cleanup_jaggies = cleanup_jaggies;
+                if self.params.cleanup_toggle {
+                    self.make_img();
+                }
+            }
+            Event::SliderCleanupIntensityChanged(cleanup_intensity) => {
+                self.params.cleanup_intensity = cleanup_intensity;
+            }
+            Event::SliderCleanupOrphansReleased | Event::SliderCleanupIntensityReleased => {
+                if self.params.cleanup_toggle {
+                    self.make_img();
+                }
+            }
+            Event::OutlineToggled(outline_toggle) => {
+                self.params.outline_toggle = outline_toggle;
+                self.make_img();
+            }
+            Event::OutlineSideSelected(outline_side) => {
+                self.params.outline_side = outline_side;
+                if self.params.outline_toggle {
+                    self.make_img();
+                }
+            }
+            Event::OutlineConnectivitySelected(outline_connectivity) => {
+                self.params.outline_connectivity = outline_connectivity;
+                if self.params.outline_toggle {
+                    self.make_img();
+                }
+            }
+            Event::OutlineAutoToggled(outline_auto) => {
+                self.params.outline_auto = outline_auto;
+                if self.params.outline_toggle {
+                    self.make_img();
+                }
+            }
+            Event::OutlineColorChanged(outline_color_value) => {
+                if outline_color_value.len() <= 7
+                    && outline_color_value
+                        .chars()
+                        .all(|c| c == '#' || c.is_ascii_hexdigit())
+                {
+                    self.outline_color_value = outline_color_value;
+                }
+            }
+            Event::OutlineColorSubmitted => {
+                if let Ok(outline_color) = self.outline_color_value.parse() {
+                    self.params.outline_color = outline_color;
+                    self.outline_color_value = self.params.outline_color.to_string();
+                    if self.params.outline_toggle {
+                        self.make_img();
+                    }
+                }
+            }
+            Event::PatternToggled(pattern_toggle) => {
+                self.params.pattern_toggle = pattern_toggle;
+                self.make_img();
+            }
+            Event::PatternBrandSelected(pattern_brand) => {
+                self.params.pattern_brand = pattern_brand;
+                if self.params.pattern_toggle {
                     self.make_img();
                 }
             }
+            Event::ConstraintToggled(constraint_toggle) => {
+                self.params.constraint_toggle = constraint_toggle;
+                self.make_img();
+            }
+            Event::ConstraintHardwareSelected(constraint_hardware) => {
+                self.params.constraint_hardware = constraint_hardware;
+                if self.params.constraint_toggle {
+                    self.make_img();
+                }
+            }
+            Event::DepthToggled(depth_toggle) => {
+                self.params.depth_toggle = depth_toggle;
+                self.make_img();
+            }
+            Event::DepthSystemSelected(depth_system) => {
+                self.params.depth_system = depth_system;
+                if self.params.depth_toggle {
+                    self.make_img();
+                }
+            }
+            Event::TilemapToggled(tilemap_toggle) => {
+                self.tilemap_toggle = tilemap_toggle;
+                self.count_tiles();
+            }
+            Event::SliderTilemapSizeChanged(tilemap_size) => {
+                self.tilemap_size = tilemap_size;
+            }
+            Event::SliderTilemapSizeReleased => {
+                self.count_tiles();
+            }
+            Event::TilemapFlipsToggled(tilemap_flips) => {
+                self.tilemap_flips = tilemap_flips;
+                self.count_tiles();
+            }
+            Event::TilemapFormatSelected(tilemap_format) => {
+                self.tilemap_format = tilemap_format;
+            }
+            Event::PaletteSwatchPressed(i) => match self.palette_selected {
+                Some(from) if self.palette_merging && from != i => {
+                    for result in self.results.iter_mut() {
+                        result.merge(from, i);
+                    }
+                    self.palette_entries.remove(from);
+                    self.palette_selected = Some(if i > from { i - 1 } else { i });
+                    self.palette_merging = false;
+                    self.render_result();
+                }
+                _ => {
+                    self.palette_selected = Some(i);
+                    self.palette_merging = false;
+                }
+            },
+            Event::PaletteLockPressed => {
+                if let Some(entry) = self.selected_entry_mut() {
+                    entry.locked = !entry.locked;
+                }
+            }
+            Event::PaletteMergePressed => {
+                self.palette_merging = !self.palette_merging;
+            }
+            Event::PaletteReusePressed => {
+                let mut dialog = rfd::FileDialog::new();
+                if let Some(save_path) = self.save_path.as_ref() {
+                    dialog = dialog.set_directory(save_path);
+                }
+                // Locked colors are final, they are reused as the other
+                // image shows them
+                let reused = dialog.pick_file().and_then(|path| {
+                    let mut reused = pipeline::shared_palette(&[path], &self.params, &[]).ok()?;
+                    pipeline::recolor_palette(&mut reused, &self.params).ok()?;
+                    Some(reused)
+                });
+                if let Some(reused) = reused.filter(|reused| !reused.is_empty()) {
+                    // Remapped as is, no free color being added
+                    self.params.kcolors = reused.len().min(u8::MAX as usize) as u8;
+                    self.palette_entries = reused
+                        .into_iter()
+                        .map(|color| PaletteEntry::new(color, true))
+                        .collect();
+                    self.make_img();
+                }
+            }
+            Event::SliderPaletteRedChanged(r) => {
+                if let Some(entry) = self.selected_entry_mut() {
+                    entry.color.r = r;
+                    entry.locked = true;
+                }
+            }
+            Event::SliderPaletteGreenChanged(g) => {
+                if let Some(entry) = self.selected_entry_mut() {
+                    entry.color.g = g;
+                    entry.locked = true;
+                }
+            }
+            Event::SliderPaletteBlueChanged(b) => {
+                if let Some(entry) = self.selected_entry_mut() {
+                    entry.color.b = b;
+                    entry.locked = true;
+                }
+            }
+            Event::SliderPaletteColorReleased => {
+                self.render_result();
+            }
+            Event::AnalysisToggled(analysis_toggle) => {
+                self.analysis_toggle = analysis_toggle;
+            }
             Event::SavePressed | Event::SaveAsPressed => {
                 let select_file = (matches!(evt, Event::SavePressed) && self.save_file.is_none())
                     || matches!(evt, Event::SaveAsPressed);
@@ -241,24 +1053,38 @@ impl Sandbox for Easel {
                     self.save_file = save_file;
                 }
 
-                let save_wtr = self.save_file.as_ref().map(|f| {
-                    fs::OpenOptions::new()
-                        .write(true)
-                        .truncate(true)
-                        .create(true)
-                        .open(f)
-                        .ok()
-                });
-
-                match (save_wtr, &self.img_handle.data()) {
-                    (Some(Some(ref mut save_wtr)), ImageData::Bytes(bytes)) => {
-                        if save_wtr.write_all(bytes).is_ok() {
-                            self.saved = true;
-                        }
+                if let Some(save_file) = self.save_file.as_ref() {
+                    if self.export(save_file).is_ok() {
+                        self.saved = true;
                     }
-                    _ => (),
                 }
             }
+            Event::AnimationPlayPressed => {
+                self.animation_playing = !self.animation_playing;
+            }
+            Event::AnimationTicked(_) => {
+                if !self.frame_handles.is_empty() {
+                    self.frame_index = (self.frame_index + 1) % self.frame_handles.len();
+                    self.img_handle = self.frame_handles[self.frame_index].clone();
+                }
+            }
+            Event::ExportFormatSelected(export_format) => {
+                self.export_format = export_format;
+            }
+            Event::AnsiStyleSelected(ansi_style) => {
+                self.ansi_style = ansi_style;
+            }
+        }
+
+        Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<Event> {
+        match self.delays.get(self.frame_index) {
+            Some(&delay) if self.animation_playing && self.is_animated() => {
+                time::every(delay).map(Event::AnimationTicked)
+            }
+            _ => Subscription::none(),
         }
     }
 
@@ -293,6 +1119,17 @@ impl Sandbox for Easel {
             .push(choose_img)
             .push(save_img_as)
             .push(save_img)
+            // Colors of the `.ans` exports
+            .push(Text::new("ANSI"))
+            .push(
+                PickList::new(
+                    &mut self.ansi_style_list,
+                    &Style::ANSI[..],
+                    Some(self.ansi_style),
+                    Event::AnsiStyleSelected,
+                )
+                .style(self.theme),
+            )
             .push(Space::with_width(Length::Fill))
             .push(change_layout)
             .push(change_theme)
@@ -302,177 +1139,1667 @@ impl Sandbox for Easel {
         let sub_name_width = 105;
         let val_width = 50;
 
-        let pixelize = Row::new()
-            .padding(PADDING)
+        let mut animation = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center);
+        if self.is_animated() {
+            animation = animation
+                .push(Text::new("Animation").width(Length::Units(main_name_width)))
+                .push(
+                    Button::new(
+                        &mut self.animation_button,
+                        Text::new(if self.animation_playing {
+                            "pause"
+                        } else {
+                            "play"
+                        }),
+                    )
+                    .on_press(Event::AnimationPlayPressed)
+                    .style(self.theme),
+                )
+                .push(
+                    Text::new(format!("{}/{}", self.frame_index + 1, self.results.len()))
+                        .width(Length::Units(val_width))
+                        .font(FONT_PIX_L),
+                )
+                .push(
+                    PickList::new(
+                        &mut self.export_format_list,
+                        &Format::ALL[..],
+                        Some(self.export_format),
+                        Event::ExportFormatSelected,
+                    )
+                    .style(self.theme),
+                )
+                .push(Space::with_width(Length::Fill));
+        }
+
+        let mut batch = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center);
+        if self.batch.len() > 1 {
+            let current = self
+                .batch
+                .iter()
+                .position(|path| Some(path) == self.src_path.as_ref())
+                .unwrap_or(0);
+            let name = self.batch[current]
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            batch = batch
+                .push(Text::new("Batch").width(Length::Units(main_name_width)))
+                .push(
+                    Button::new(&mut self.batch_button, Text::new("next"))
+                        .on_press(Event::BatchNextPressed)
+                        .style(self.theme),
+                )
+                .push(
+                    Text::new(format!("{}/{}", current + 1, self.batch.len()))
+                        .width(Length::Units(val_width))
+                        .font(FONT_PIX_L),
+                )
+                .push(Text::new(name).width(Length::Fill));
+        }
+
+        let transform_orientation = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(
+                PickList::new(
+                    &mut self.rotation_list,
+                    &Rotation::ALL[..],
+                    Some(self.params.rotation),
+                    Event::RotationSelected,
+                )
+                .style(self.theme),
+            )
+            .push(
+                Checkbox::new(
+                    self.params.flip_horizontal,
+                    "flip x",
+                    Event::FlipHorizontalToggled,
+                )
+                .spacing(10)
+                .style(self.theme),
+            )
+            .push(
+                Checkbox::new(
+                    self.params.flip_vertical,
+                    "flip y",
+                    Event::FlipVerticalToggled,
+                )
+                .spacing(10)
+                .style(self.theme),
+            );
+
+        let mut transform_crop = Row::new().spacing(10).align_items(Align::Center).push(
+            Checkbox::new(self.params.crop_toggle, "crop", Event::CropToggled)
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.crop_toggle {
+            transform_crop = transform_crop
+                .push(
+                    PickList::new(
+                        &mut self.crop_aspect_list,
+                        &Aspect::ALL[..],
+                        Some(self.params.crop_aspect),
+                        Event::CropAspectSelected,
+                    )
+                    .style(self.theme),
+                )
+                .push(Space::with_width(Length::Fill))
+                .push(
+                    Button::new(
+                        &mut self.crop_edit_button,
+                        Text::new(if self.crop_editing { "done" } else { "edit" }),
+                    )
+                    .on_press(Event::CropEditPressed)
+                    .style(self.theme),
+                );
+        }
+
+        let transform = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Transform").width(Length::Units(main_name_width)))
+            .push(
+                Column::new()
+                    .spacing(5)
+                    .push(transform_orientation)
+                    .push(transform_crop),
+            );
+
+        let sheet_slicing = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("slice by").width(Length::Units(sub_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.sheet_slicing_list,
+                    &Slicing::ALL[..],
+                    Some(self.params.sheet_slicing),
+                    Event::SheetSlicingSelected,
+                )
+                .style(self.theme),
+            );
+
+        let (sheet_width, sheet_height) = match self.params.sheet_slicing {
+            Slicing::Size => {
+                let (cell_width, cell_height) = self.params.sheet_cell_size;
+                (
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("cell width").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.sheet_width_slider,
+                                1..=256,
+                                cell_width,
+                                Event::SliderSheetCellWidthChanged,
+                            )
+                            .on_release(Event::SliderSheetCellsReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(cell_width.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("cell height").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.sheet_height_slider,
+                                1..=256,
+                                cell_height,
+                                Event::SliderSheetCellHeightChanged,
+                            )
+                            .on_release(Event::SliderSheetCellsReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(cell_height.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                )
+            }
+            Slicing::Count => {
+                let (columns, rows) = self.params.sheet_cell_count;
+                (
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("columns").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.sheet_width_slider,
+                                1..=32,
+                                columns,
+                                Event::SliderSheetColumnsChanged,
+                            )
+                            .on_release(Event::SliderSheetCellsReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(columns.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("rows").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.sheet_height_slider,
+                                1..=32,
+                                rows,
+                                Event::SliderSheetRowsChanged,
+                            )
+                            .on_release(Event::SliderSheetCellsReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(rows.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                )
+            }
+        };
+
+        let sheet_padding = Row::new()
+            .spacing(10)
+            .push(Text::new("padding").width(Length::Units(sub_name_width)))
+            .push(
+                Slider::new(
+                    &mut self.sheet_padding_slider,
+                    0..=16,
+                    self.params.sheet_padding,
+                    Event::SliderSheetPaddingChanged,
+                )
+                .on_release(Event::SliderSheetPaddingReleased)
+                .width(Length::Fill)
+                .style(self.theme),
+            )
+            .push(
+                Text::new(self.params.sheet_padding.to_string())
+                    .width(Length::Units(val_width))
+                    .font(FONT_PIX_L),
+            );
+
+        let sheet_atlas = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("atlas").width(Length::Units(sub_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.sheet_atlas_list,
+                    &Atlas::ALL[..],
+                    Some(self.sheet_atlas),
+                    Event::SheetAtlasSelected,
+                )
+                .style(self.theme),
+            )
+            .push(Space::with_width(Length::Fill))
+            .push(
+                Text::new(match self.sheet {
+                    Some(grid) => format!("{}x{}", grid.columns, grid.rows),
+                    None => String::new(),
+                })
+                .width(Length::Units(val_width))
+                .font(FONT_PIX_L),
+            );
+
+        let mut sheet = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.sheet_toggle, "Sheet", Event::SheetToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.sheet_toggle {
+            sheet = sheet.push(
+                Column::new()
+                    .spacing(5)
+                    .push(sheet_slicing)
+                    .push(sheet_width)
+                    .push(sheet_height)
+                    .push(sheet_padding)
+                    .push(sheet_atlas),
+            );
+        } else {
+            sheet = sheet.push(Space::with_width(Length::Fill));
+        }
+
+        let pixelize_amount = Row::new()
+            .spacing(10)
+            .push(
+                Slider::new(
+                    &mut self.pixelize_slider,
+                    0..=99,
+                    self.params.pixelize,
+                    Event::SliderPixelizeChanged,
+                )
+                .on_release(Event::SliderPixelizeReleased)
+                .width(Length::Fill)
+                .style(self.theme),
+            )
+            .push(
+                Text::new(&format!("{} %", self.params.pixelize))
+                    .width(Length::Units(val_width))
+                    .font(FONT_PIX_L),
+            );
+
+        let pixel_aspect = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("pixel aspect").width(Length::Units(sub_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.pixel_aspect_list,
+                    &PixelAspect::ALL[..],
+                    Some(self.params.pixel_aspect),
+                    Event::PixelAspectSelected,
+                )
+                .style(self.theme),
+            );
+
+        let mut pixelize_controls = Column::new()
+            .spacing(5)
+            .push(pixelize_amount)
+            .push(pixel_aspect);
+
+        if self.params.pixel_aspect == PixelAspect::Custom {
+            let (aspect_width, aspect_height) = self.params.pixel_aspect_custom;
+            pixelize_controls = pixelize_controls
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("width").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.pixel_aspect_width_slider,
+                                1..=16,
+                                aspect_width,
+                                Event::SliderPixelAspectWidthChanged,
+                            )
+                            .on_release(Event::SliderPixelAspectReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(aspect_width.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new("height").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(
+                                &mut self.pixel_aspect_height_slider,
+                                1..=16,
+                                aspect_height,
+                                Event::SliderPixelAspectHeightChanged,
+                            )
+                            .on_release(Event::SliderPixelAspectReleased)
+                            .width(Length::Fill)
+                            .style(self.theme),
+                        )
+                        .push(
+                            Text::new(aspect_height.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        ),
+                );
+        }
+
+        let pixelize = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Pixelize").width(Length::Units(main_name_width)))
+            .push(pixelize_controls);
+
+        // Offsets beyond a block wrap around, the slider stops at the last one
+        let (block_x, block_y) = pipeline::block_size(&self.params);
+        let grid_max = |block: usize| (block - 1).clamp(1, u16::MAX as usize) as u16;
+        let (grid_max_x, grid_max_y) = (grid_max(block_x), grid_max(block_y));
+
+        let grid_x = Row::new()
+            .spacing(10)
+            .push(Text::new("offset x").width(Length::Units(sub_name_width)))
+            .push(
+                Slider::new(
+                    &mut self.grid_x_slider,
+                    0..=grid_max_x,
+                    self.params.grid_x.min(grid_max_x),
+                    Event::SliderGridXChanged,
+                )
+                .width(Length::Fill)
+                .style(self.theme),
+            )
+            .push(
+                Text::new(self.params.grid_x.to_string())
+                    .width(Length::Units(val_width))
+                    .font(FONT_PIX_L),
+            );
+
+        let grid_y = Row::new()
+            .spacing(10)
+            .push(Text::new("offset y").width(Length::Units(sub_name_width)))
+            .push(
+                Slider::new(
+                    &mut self.grid_y_slider,
+                    0..=grid_max_y,
+                    self.params.grid_y.min(grid_max_y),
+                    Event::SliderGridYChanged,
+                )
+                .width(Length::Fill)
+                .style(self.theme),
+            )
+            .push(
+                Text::new(self.params.grid_y.to_string())
+                    .width(Length::Units(val_width))
+                    .font(FONT_PIX_L),
+            );
+
+        let grid = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Grid").width(Length::Units(main_name_width)))
+            .push(Column::new().push(grid_x).push(grid_y));
+
+        let kcolors = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Colors").width(Length::Units(main_name_width)))
+            .push(
+                Slider::new(
+                    &mut self.kcolors_slider,
+                    1..=64,
+                    self.params.kcolors,
+                    Event::SliderKcolorsChanged,
+                )
+                .on_release(Event::SliderKcolorsReleased)
+                .width(Length::Fill)
+                .style(self.theme),
+            )
+            .push(
+                Text::new(self.params.kcolors.to_string())
+                    .width(Length::Units(val_width))
+                    .font(FONT_PIX_L),
+            );
+
+        let seed = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Seed").width(Length::Units(main_name_width)))
+            .push(
+                TextInput::new(
+                    &mut self.seed_input,
+                    "0",
+                    &self.seed_value,
+                    Event::SeedChanged,
+                )
+                .on_submit(Event::SeedSubmitted)
+                .padding(5)
+                .width(Length::Fill)
+                .font(FONT_PIX_L)
+                .style(self.theme),
+            )
+            .push(
+                Button::new(&mut self.seed_button, Text::new("shuffle"))
+                    .on_press(Event::SeedPressed)
+                    .style(self.theme),
+            );
+
+        let quantizer = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Quantizer").width(Length::Units(main_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.quantizer_list,
+                    Quantizer::AVAILABLE,
+                    Some(self.params.quantizer),
+                    Event::QuantizerSelected,
+                )
+                .style(self.theme),
+            )
+            .push(Space::with_width(Length::Fill));
+
+        let mut cluster_space = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Cluster in").width(Length::Units(main_name_width)));
+
+        // Only k-means++ clusters in the chosen space, the others in RGB
+        cluster_space = if self.params.quantizer.uses_space() {
+            cluster_space.push(
+                PickList::new(
+                    &mut self.cluster_space_list,
+                    &ColorSpace::ALL[..],
+                    Some(self.params.cluster_space),
+                    Event::ClusterSpaceSelected,
+                )
+                .style(self.theme),
+            )
+        } else {
+            cluster_space.push(Text::new(ColorSpace::Rgb.to_string()).font(FONT_PIX_L))
+        }
+        .push(Space::with_width(Length::Fill));
+
+        let remap_metric = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("Remap by").width(Length::Units(main_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.remap_metric_list,
+                    &Metric::ALL[..],
+                    Some(self.params.remap_metric),
+                    Event::RemapMetricSelected,
+                )
+                .style(self.theme),
+            )
+            .push(Space::with_width(Length::Fill));
+
+        let theme = self.theme;
+        let mut stack = Column::new();
+        for (i, (stage, widgets)) in self
+            .params
+            .stack
+            .iter()
+            .zip(self.stage_widgets.iter_mut())
+            .enumerate()
+        {
+            let kind = stage.adjustment.kind();
+            let StageWidgets {
+                sliders: [first_slider, second_slider, third_slider],
+                channel_sliders,
+                up_button,
+                down_button,
+                duplicate_button,
+                remove_button,
+            } = widgets;
+
+            let mut controls = Column::new();
+            match stage.adjustment {
+                _ if !stage.enabled => {}
+                Adjustment::Downscale => {
+                    controls = controls.push(Text::new("pixelize").font(FONT_PIX_L));
+                }
+                Adjustment::Quantize => {
+                    controls = controls.push(Text::new("kcolors").font(FONT_PIX_L));
+                }
+                Adjustment::Levels {
+                    per_channel,
+                    levels,
+                    channels,
+                } => {
+                    let level_per_channel = Row::new().spacing(10).push(
+                        Checkbox::new(per_channel, "per channel", move |v| {
+                            Event::LevelPerChannelToggled(i, v)
+                        })
+                        .spacing(10)
+                        .style(theme),
+                    );
+                    controls = controls.push(level_per_channel);
+
+                    if per_channel {
+                        for ((channel, sliders), levels) in Channel::ALL
+                            .iter()
+                            .copied()
+                            .zip(channel_sliders.iter_mut())
+                            .zip(channels.iter().copied())
+                        {
+                            let black = Row::new()
+                                .spacing(10)
+                                .push(Text::new("black").width(Length::Units(sub_name_width)))
+                                .push(
+                                    Slider::new(
+                                        &mut sliders.black,
+                                        0..=100,
+                                        levels.black,
+                                        move |v| {
+                                            Event::SliderLevelChannelBlackChanged(i, channel, v)
+                                        },
+                                    )
+                                    .on_release(Event::SliderStageReleased)
+                                    .width(Length::Fill)
+                                    .style(theme),
+                                )
+                                .push(
+                                    Text::new(format!("{} %", levels.black))
+                                        .width(Length::Units(val_width))
+                                        .font(FONT_PIX_L),
+                                );
+
+                            let white = Row::new()
+                                .spacing(10)
+                                .push(Text::new("white").width(Length::Units(sub_name_width)))
+                                .push(
+                                    Slider::new(
+                                        &mut sliders.white,
+                                        0..=100,
+                                        levels.white,
+                                        move |v| {
+                                            Event::SliderLevelChannelWhiteChanged(i, channel, v)
+                                        },
+                                    )
+                                    .on_release(Event::SliderStageReleased)
+                                    .width(Length::Fill)
+                                    .style(theme),
+                                )
+                                .push(
+                                    Text::new(format!("{} %", levels.white))
+                                        .width(Length::Units(val_width))
+                                        .font(FONT_PIX_L),
+                                );
+
+                            let gamma = Row::new()
+                                .spacing(10)
+                                .push(Text::new("gamma").width(Length::Units(sub_name_width)))
+                                .push(
+                                    Slider::new(
+                                        &mut sliders.gamma,
+                                        10..=400,
+                                        levels.gamma,
+                                        move |v| {
+                                            Event::SliderLevelChannelGammaChanged(i, channel, v)
+                                        },
+                                    )
+                                    .on_release(Event::SliderStageReleased)
+                                    .width(Length::Fill)
+                                    .style(theme),
+                                )
+                                .push(
+                                    Text::new(format!("{:.2}", levels.gamma as f32 / 100.0))
+                                        .width(Length::Units(val_width))
+                                        .font(FONT_PIX_L),
+                                );
+
+                            controls = controls
+                                .push(Text::new(channel.to_string()).font(FONT_PIX_L))
+                                .push(black)
+                                .push(white)
+                                .push(gamma);
+                        }
+                    } else {
+                        let black = Row::new()
+                            .spacing(10)
+                            .push(Text::new("black").width(Length::Units(sub_name_width)))
+                            .push(
+                                Slider::new(first_slider, 0..=100, levels.black, move |v| {
+                                    Event::SliderLevelBlackChanged(i, v)
+                                })
+                                .on_release(Event::SliderStageReleased)
+                                .width(Length::Fill)
+                                .style(theme),
+                            )
+                            .push(
+                                Text::new(format!("{} %", levels.black))
+                                    .width(Length::Units(val_width))
+                                    .font(FONT_PIX_L),
+                            );
+
+                        let white = Row::new()
+                            .spacing(10)
+                            .push(Text::new("white").width(Length::Units(sub_name_width)))
+                            .push(
+                                Slider::new(second_slider, 0..=100, levels.white, move |v| {
+                                    Event::SliderLevelWhiteChanged(i, v)
+                                })
+                                .on_release(Event::SliderStageReleased)
+                                .width(Length::Fill)
+                                .style(theme),
+                            )
+                            .push(
+                                Text::new(format!("{} %", levels.white))
+                                    .width(Length::Units(val_width))
+                                    .font(FONT_PIX_L),
+                            );
+
+                        let gamma = Row::new()
+                            .spacing(10)
+                            .push(Text::new("gamma").width(Length::Units(sub_name_width)))
+                            .push(
+                                Slider::new(third_slider, 10..=400, levels.gamma, move |v| {
+                                    Event::SliderLevelGammaChanged(i, v)
+                                })
+                                .on_release(Event::SliderStageReleased)
+                                .width(Length::Fill)
+                                .style(theme),
+                            )
+                            .push(
+                                Text::new(format!("{:.2}", levels.gamma as f32 / 100.0))
+                                    .width(Length::Units(val_width))
+                                    .font(FONT_PIX_L),
+                            );
+
+                        controls = controls.push(black).push(white).push(gamma);
+                    }
+
+                    match self.stage_histograms.get(i) {
+                        Some(histogram) if !histogram.is_empty() => {
+                            let chart = if per_channel {
+                                histogram::Chart {
+                                    histogram: histogram.clone(),
+                                    channels: histogram::Channels::Rgb,
+                                    markers: channels
+                                        .iter()
+                                        .map(|levels| (levels.black, levels.white))
+                                        .collect(),
+                                }
+                            } else {
+                                histogram::Chart {
+                                    histogram: histogram.clone(),
+                                    channels: histogram::Channels::Luma,
+                                    markers: vec![(levels.black, levels.white)],
+                                }
+                            };
+                            controls = controls.push(
+                                Canvas::new(chart)
+                                    .width(Length::Fill)
+                                    .height(Length::Units(60)),
+                            );
+                        }
+                        _ => {}
+                    }
+                }
+                Adjustment::Modulate {
+                    brightness,
+                    saturation,
+                    hue,
+                } => {
+                    let brightness = Row::new()
+                        .spacing(10)
+                        .push(Text::new("brightness").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(first_slider, 0..=200, brightness, move |v| {
+                                Event::SliderModulateBrightnessChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(brightness.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    let saturation = Row::new()
+                        .spacing(10)
+                        .push(Text::new("saturation").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(second_slider, 0..=200, saturation, move |v| {
+                                Event::SliderModulateSaturationChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(saturation.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    let hue = Row::new()
+                        .spacing(10)
+                        .push(Text::new("hue").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(third_slider, 0..=200, hue, move |v| {
+                                Event::SliderModulateHueChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(hue.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    controls = controls.push(brightness).push(saturation).push(hue);
+                }
+                Adjustment::Contrast {
+                    sigmoidal,
+                    amount,
+                    midpoint,
+                } => {
+                    let contrast_sigmoidal = Row::new().spacing(10).push(
+                        Checkbox::new(sigmoidal, "sigmoidal", move |v| {
+                            Event::ContrastSigmoidalToggled(i, v)
+                        })
+                        .spacing(10)
+                        .style(theme),
+                    );
+
+                    let amount = Row::new()
+                        .spacing(10)
+                        .push(Text::new("amount").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(first_slider, 0..=200, amount, move |v| {
+                                Event::SliderContrastAmountChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(amount.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    let midpoint = Row::new()
+                        .spacing(10)
+                        .push(Text::new("midpoint").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(second_slider, 0..=100, midpoint, move |v| {
+                                Event::SliderContrastMidpointChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(format!("{} %", midpoint))
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    controls = controls.push(contrast_sigmoidal).push(amount);
+                    if sigmoidal {
+                        controls = controls.push(midpoint);
+                    }
+                }
+                Adjustment::Unsharp {
+                    sigma,
+                    amount,
+                    threshold,
+                } => {
+                    let sigma = Row::new()
+                        .spacing(10)
+                        .push(Text::new("sigma").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(first_slider, 1..=100, sigma, move |v| {
+                                Event::SliderUnsharpSigmaChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(format!("{:.1}", sigma as f32 / 10.0))
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    let amount = Row::new()
+                        .spacing(10)
+                        .push(Text::new("amount").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(second_slider, 0..=200, amount, move |v| {
+                                Event::SliderUnsharpAmountChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(format!("{} %", amount))
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    let threshold = Row::new()
+                        .spacing(10)
+                        .push(Text::new("threshold").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(third_slider, 0..=100, threshold, move |v| {
+                                Event::SliderUnsharpThresholdChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(format!("{} %", threshold))
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    controls = controls.push(sigma).push(amount).push(threshold);
+                }
+                Adjustment::Blur { sigma } => {
+                    let sigma = Row::new()
+                        .spacing(10)
+                        .push(Text::new("sigma").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(first_slider, 1..=100, sigma, move |v| {
+                                Event::SliderBlurSigmaChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(format!("{:.1}", sigma as f32 / 10.0))
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    controls = controls.push(sigma);
+                }
+                Adjustment::Posterize { levels } => {
+                    let levels = Row::new()
+                        .spacing(10)
+                        .push(Text::new("levels").width(Length::Units(sub_name_width)))
+                        .push(
+                            Slider::new(first_slider, 2..=32, levels, move |v| {
+                                Event::SliderPosterizeLevelsChanged(i, v)
+                            })
+                            .on_release(Event::SliderStageReleased)
+                            .width(Length::Fill)
+                            .style(theme),
+                        )
+                        .push(
+                            Text::new(levels.to_string())
+                                .width(Length::Units(val_width))
+                                .font(FONT_PIX_L),
+                        );
+
+                    controls = controls.push(levels);
+                }
+            }
+
+            let mut actions = Row::new()
+                .spacing(10)
+                .push(
+                    Button::new(up_button, Text::new("up"))
+                        .on_press(Event::StageUpPressed(i))
+                        .style(theme),
+                )
+                .push(
+                    Button::new(down_button, Text::new("down"))
+                        .on_press(Event::StageDownPressed(i))
+                        .style(theme),
+                );
+            if !kind.is_marker() {
+                actions = actions
+                    .push(
+                        Button::new(duplicate_button, Text::new("copy"))
+                            .on_press(Event::StageDuplicatePressed(i))
+                            .style(theme),
+                    )
+                    .push(
+                        Button::new(remove_button, Text::new("remove"))
+                            .on_press(Event::StageRemovePressed(i))
+                            .style(theme),
+                    );
+            }
+
+            let name: Element<Event> = if kind.is_marker() {
+                Text::new(kind.to_string())
+                    .width(Length::Units(main_name_width))
+                    .into()
+            } else {
+                Checkbox::new(stage.enabled, kind.to_string(), move |enabled| {
+                    Event::StageToggled(i, enabled)
+                })
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(theme)
+                .into()
+            };
+
+            stack = stack.push(
+                Row::new()
+                    .padding(PADDING)
+                    .spacing(10)
+                    .push(name)
+                    .push(controls.push(actions)),
+            );
+        }
+
+        let stack_add = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Adjustments").width(Length::Units(main_name_width)))
+            .push(
+                PickList::new(
+                    &mut self.stage_add_list,
+                    &Kind::ALL[..],
+                    None,
+                    Event::StageAddSelected,
+                )
+                .style(self.theme),
+            )
+            .push(Space::with_width(Length::Fill));
+
+        let alpha_threshold = Row::new()
             .spacing(10)
-            .push(Text::new("Pixelize").width(Length::Units(main_name_width)))
+            .push(Text::new("threshold").width(Length::Units(sub_name_width)))
             .push(
                 Slider::new(
-                    &mut self.pixelize_slider,
-                    0..=99,
-                    self.pixelize,
-                    Event::SliderPixelizeChanged,
+                    &mut self.alpha_threshold_slider,
+                    0..=100,
+                    self.params.alpha_threshold,
+                    Event::SliderAlphaThresholdChanged,
                 )
-                .on_release(Event::SliderPixelizeReleased)
+                .on_release(Event::SliderAlphaThresholdReleased)
                 .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(&format!("{} %", self.pixelize))
+                Text::new(format!("{} %", self.params.alpha_threshold))
                     .width(Length::Units(val_width))
                     .font(FONT_PIX_L),
             );
 
-        let kcolors = Row::new()
-            .padding(PADDING)
+        let alpha_exclude = Row::new().spacing(10).push(
+            Checkbox::new(
+                self.params.alpha_exclude,
+                "exclude from palette",
+                Event::AlphaExcludeToggled,
+            )
             .spacing(10)
-            .push(Text::new("Colors").width(Length::Units(main_name_width)))
+            .style(self.theme),
+        );
+
+        let alpha_checkerboard = Row::new().spacing(10).push(
+            Checkbox::new(
+                self.alpha_checkerboard,
+                "checkerboard",
+                Event::AlphaCheckerboardToggled,
+            )
+            .spacing(10)
+            .style(self.theme),
+        );
+
+        let mut alpha = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.alpha_toggle, "Alpha", Event::AlphaToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.alpha_toggle {
+            alpha = alpha.push(
+                Column::new()
+                    .push(alpha_threshold)
+                    .push(alpha_exclude)
+                    .push(alpha_checkerboard),
+            );
+        } else {
+            alpha = alpha.push(Space::with_width(Length::Fill));
+        }
+
+        let background_removal = Row::new().spacing(10).align_items(Align::Center).push(
+            PickList::new(
+                &mut self.background_removal_list,
+                &Removal::ALL[..],
+                Some(self.params.background_removal),
+                Event::BackgroundRemovalSelected,
+            )
+            .style(self.theme),
+        );
+
+        let background_key = Row::new()
+            .spacing(10)
+            .align_items(Align::Center)
+            .push(Text::new("key").width(Length::Units(sub_name_width)))
+            .push(
+                Container::new(Space::new(Length::Units(24), Length::Units(24)))
+                    .style(style::Swatch(self.params.background_key.to_color())),
+            )
+            .push(Text::new(self.params.background_key.to_string()).font(FONT_PIX_L))
+            .push(Space::with_width(Length::Fill))
+            .push(
+                Button::new(
+                    &mut self.background_pick_button,
+                    Text::new(if self.background_picking {
+                        "cancel"
+                    } else {
+                        "pick"
+                    }),
+                )
+                .on_press(Event::BackgroundPickPressed)
+                .style(self.theme),
+            );
+
+        let background_tolerance = Row::new()
+            .spacing(10)
+            .push(Text::new("tolerance").width(Length::Units(sub_name_width)))
             .push(
                 Slider::new(
-                    &mut self.kcolors_slider,
-                    1..=64,
-                    self.kcolors,
-                    Event::SliderKcolorsChanged,
+                    &mut self.background_tolerance_slider,
+                    0..=100,
+                    self.params.background_tolerance,
+                    Event::SliderBackgroundToleranceChanged,
                 )
-                .on_release(Event::SliderKcolorsReleased)
+                .on_release(Event::SliderBackgroundToleranceReleased)
                 .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(self.kcolors.to_string())
+                Text::new(format!("{} %", self.params.background_tolerance))
                     .width(Length::Units(val_width))
                     .font(FONT_PIX_L),
             );
 
-        let level_black = Row::new()
+        let mut background = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(
+                self.params.background_toggle,
+                "Background",
+                Event::BackgroundToggled,
+            )
+            .width(Length::Units(main_name_width))
+            .spacing(10)
+            .style(self.theme),
+        );
+
+        if self.params.background_toggle {
+            background = background.push(
+                Column::new()
+                    .spacing(5)
+                    .push(background_removal)
+                    .push(background_key)
+                    .push(background_tolerance),
+            );
+        } else {
+            background = background.push(Space::with_width(Length::Fill));
+        }
+
+        let cleanup_orphans = Row::new()
             .spacing(10)
-            .push(Text::new("black").width(Length::Units(sub_name_width)))
+            .push(Text::new("orphans").width(Length::Units(sub_name_width)))
             .push(
                 Slider::new(
-                    &mut self.level_black_slider,
-                    0..=100,
-                    self.level_black,
-                    Event::SliderLevelBlackChanged,
+                    &mut self.cleanup_orphans_slider,
+                    0..=16,
+                    self.params.cleanup_orphans,
+                    Event::SliderCleanupOrphansChanged,
                 )
-                .on_release(Event::SliderLevelBlackReleased)
+                .on_release(Event::SliderCleanupOrphansReleased)
                 .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(format!("{} %", self.level_black))
+                Text::new(self.params.cleanup_orphans.to_string())
                     .width(Length::Units(val_width))
                     .font(FONT_PIX_L),
             );
 
-        let level_white = Row::new()
+        let cleanup_jaggies = Row::new().spacing(10).push(
+            Checkbox::new(
+                self.params.cleanup_jaggies,
+                "jaggies",
+                Event::CleanupJaggiesToggled,
+            )
+            .spacing(10)
+            .style(self.theme),
+        );
+
+        let cleanup_intensity = Row::new()
             .spacing(10)
-            .push(Text::new("white").width(Length::Units(sub_name_width)))
+            .push(Text::new("intensity").width(Length::Units(sub_name_width)))
             .push(
                 Slider::new(
-                    &mut self.level_white_slider,
-                    0..=100,
-                    self.level_white,
-                    Event::SliderLevelWhiteChanged,
+                    &mut self.cleanup_intensity_slider,
+                    1..=5,
+                    self.params.cleanup_intensity,
+                    Event::SliderCleanupIntensityChanged,
                 )
-                .on_release(Event::SliderLevelWhiteReleased)
+                .on_release(Event::SliderCleanupIntensityReleased)
                 .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(format!("{} %", self.level_white))
+                Text::new(self.params.cleanup_intensity.to_string())
                     .width(Length::Units(val_width))
                     .font(FONT_PIX_L),
             );
 
-        let mut levels = Row::new().padding(PADDING).spacing(10).push(
-            Checkbox::new(self.level_toggle, "Levels", Event::LevelToggled)
+        let mut cleanup = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.cleanup_toggle, "Cleanup", Event::CleanupToggled)
                 .width(Length::Units(main_name_width))
                 .spacing(10)
                 .style(self.theme),
         );
 
-        if self.level_toggle {
-            levels = levels.push(Column::new().push(level_black).push(level_white));
+        if self.params.cleanup_toggle {
+            cleanup = cleanup.push(
+                Column::new()
+                    .push(cleanup_orphans)
+                    .push(cleanup_jaggies)
+                    .push(cleanup_intensity),
+            );
         } else {
-            levels = levels.push(Space::with_width(Length::Fill));
+            cleanup = cleanup.push(Space::with_width(Length::Fill));
         }
 
-        let modulate_brightness = Row::new()
+        let outline_shape = Row::new()
             .spacing(10)
-            .push(Text::new("brightness").width(Length::Units(sub_name_width)))
+            .align_items(Align::Center)
             .push(
-                Slider::new(
-                    &mut self.modulate_brightness_slider,
-                    0..=200,
-                    self.modulate_brightness,
-                    Event::SliderModulateBrightnessChanged,
+                PickList::new(
+                    &mut self.outline_side_list,
+                    &Side::ALL[..],
+                    Some(self.params.outline_side),
+                    Event::OutlineSideSelected,
                 )
-                .on_release(Event::SliderModulateBrightnessReleased)
-                .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(self.modulate_brightness.to_string())
-                    .width(Length::Units(val_width))
-                    .font(FONT_PIX_L),
+                PickList::new(
+                    &mut self.outline_connectivity_list,
+                    &Connectivity::ALL[..],
+                    Some(self.params.outline_connectivity),
+                    Event::OutlineConnectivitySelected,
+                )
+                .style(self.theme),
+            );
+
+        let mut outline_color = Row::new().spacing(10).align_items(Align::Center).push(
+            Checkbox::new(self.params.outline_auto, "auto", Event::OutlineAutoToggled)
+                .width(Length::Units(sub_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if !self.params.outline_auto {
+            outline_color = outline_color
+                .push(
+                    Container::new(Space::new(Length::Units(24), Length::Units(24)))
+                        .style(style::Swatch(self.params.outline_color.to_color())),
+                )
+                .push(
+                    TextInput::new(
+                        &mut self.outline_color_input,
+                        "#000000",
+                        &self.outline_color_value,
+                        Event::OutlineColorChanged,
+                    )
+                    .on_submit(Event::OutlineColorSubmitted)
+                    .padding(5)
+                    .width(Length::Fill)
+                    .font(FONT_PIX_L)
+                    .style(self.theme),
+                );
+        }
+
+        let mut outline = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.outline_toggle, "Outline", Event::OutlineToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.outline_toggle {
+            outline = outline.push(
+                Column::new()
+                    .spacing(5)
+                    .push(outline_shape)
+                    .push(outline_color),
+            );
+        } else {
+            outline = outline.push(Space::with_width(Length::Fill));
+        }
+
+        let mut pattern = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.pattern_toggle, "Pattern", Event::PatternToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.pattern_toggle {
+            pattern = pattern.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        PickList::new(
+                            &mut self.pattern_brand_list,
+                            &Brand::ALL[..],
+                            Some(self.params.pattern_brand),
+                            Event::PatternBrandSelected,
+                        )
+                        .style(self.theme),
+                    )
+                    .push(Text::new(format!("{} colors", self.palette_entries.len()))),
+            );
+        } else {
+            pattern = pattern.push(Space::with_width(Length::Fill));
+        }
+
+        let mut constraint = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(
+                self.params.constraint_toggle,
+                "Hardware",
+                Event::ConstraintToggled,
+            )
+            .width(Length::Units(main_name_width))
+            .spacing(10)
+            .style(self.theme),
+        );
+
+        if self.params.constraint_toggle {
+            let hardware = self.params.constraint_hardware;
+            let area = match hardware.cell() {
+                Some((width, height)) => format!("{}x{}", width, height),
+                None => "screen".to_string(),
+            };
+            constraint = constraint.push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        PickList::new(
+                            &mut self.constraint_hardware_list,
+                            &Hardware::ALL[..],
+                            Some(hardware),
+                            Event::ConstraintHardwareSelected,
+                        )
+                        .style(self.theme),
+                    )
+                    .push(Text::new(format!(
+                        "{} colors per {}",
+                        hardware.colors(),
+                        area
+                    ))),
+            );
+        } else {
+            constraint = constraint.push(Space::with_width(Length::Fill));
+        }
+
+        let mut depth = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.params.depth_toggle, "Depth", Event::DepthToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.params.depth_toggle {
+            depth = depth.push(
+                PickList::new(
+                    &mut self.depth_system_list,
+                    &Depth::ALL[..],
+                    Some(self.params.depth_system),
+                    Event::DepthSystemSelected,
+                )
+                .style(self.theme),
             );
+        } else {
+            depth = depth.push(Space::with_width(Length::Fill));
+        }
 
-        let modulate_saturation = Row::new()
+        let tilemap_size = Row::new()
             .spacing(10)
-            .push(Text::new("saturation").width(Length::Units(sub_name_width)))
+            .push(Text::new("tile size").width(Length::Units(sub_name_width)))
             .push(
                 Slider::new(
-                    &mut self.modulate_saturation_slider,
-                    0..=200,
-                    self.modulate_saturation,
-                    Event::SliderModulateSaturationChanged,
+                    &mut self.tilemap_size_slider,
+                    4..=64,
+                    self.tilemap_size,
+                    Event::SliderTilemapSizeChanged,
                 )
-                .on_release(Event::SliderModulateSaturationReleased)
+                .on_release(Event::SliderTilemapSizeReleased)
                 .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(self.modulate_saturation.to_string())
+                Text::new(self.tilemap_size.to_string())
                     .width(Length::Units(val_width))
                     .font(FONT_PIX_L),
             );
 
-        let modulate_hue = Row::new()
+        let tilemap_format = Row::new()
             .spacing(10)
-            .push(Text::new("hue").width(Length::Units(sub_name_width)))
+            .align_items(Align::Center)
+            .push(Text::new("map").width(Length::Units(sub_name_width)))
             .push(
-                Slider::new(
-                    &mut self.modulate_hue_slider,
-                    0..=200,
-                    self.modulate_hue,
-                    Event::SliderModulateHueChanged,
+                PickList::new(
+                    &mut self.tilemap_format_list,
+                    &tilemap::Format::ALL[..],
+                    Some(self.tilemap_format),
+                    Event::TilemapFormatSelected,
                 )
-                .on_release(Event::SliderModulateHueReleased)
-                .width(Length::Fill)
                 .style(self.theme),
             )
             .push(
-                Text::new(self.modulate_hue.to_string())
-                    .width(Length::Units(val_width))
-                    .font(FONT_PIX_L),
+                Checkbox::new(self.tilemap_flips, "flips", Event::TilemapFlipsToggled)
+                    .spacing(10)
+                    .style(self.theme),
+            )
+            .push(Space::with_width(Length::Fill))
+            .push(Text::new(format!("{} tiles", self.tilemap_tiles)));
+
+        let mut tilemap = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.tilemap_toggle, "Tilemap", Event::TilemapToggled)
+                .width(Length::Units(main_name_width))
+                .spacing(10)
+                .style(self.theme),
+        );
+
+        if self.tilemap_toggle {
+            tilemap = tilemap.push(
+                Column::new()
+                    .spacing(5)
+                    .push(tilemap_size)
+                    .push(tilemap_format),
+            );
+        } else {
+            tilemap = tilemap.push(Space::with_width(Length::Fill));
+        }
+
+        let mut palette = Row::new()
+            .padding(PADDING)
+            .spacing(10)
+            .push(Text::new("Palette").width(Length::Units(main_name_width)));
+
+        if self.palette_entries.is_empty() {
+            palette = palette.push(Space::with_width(Length::Fill));
+        } else {
+            let selected = self.palette_selected;
+            let selected_entry = selected
+                .and_then(|i| self.palette_entries.get(i))
+                .map(|entry| (entry.color, entry.locked));
+            // Snapped colors are shown along with their hardware value
+            let snapped = Some(self.params.depth_system).filter(|_| self.params.depth_toggle);
+            let per_row = if snapped.is_some() { 4 } else { 8 };
+            let mut swatches = Column::new().spacing(4);
+            let mut row = Row::new().spacing(4).align_items(Align::Center);
+            for (i, entry) in self.palette_entries.iter_mut().enumerate() {
+                let notation = snapped.map(|depth| depth.notation(entry.color));
+                let swatch = Button::new(
+                    &mut entry.button,
+                    Text::new(if entry.locked { "L" } else { "" })
+                        .size(14)
+                        .width(Length::Fill)
+                        .horizontal_alignment(HorizontalAlignment::Center),
+                )
+                .padding(4)
+                .width(Length::Units(24))
+                .height(Length::Units(24))
+                .on_press(Event::PaletteSwatchPressed(i))
+                .style(style::SwatchButton {
+                    color: entry.color.to_color(),
+                    selected: selected == Some(i),
+                });
+                row = row.push(swatch);
+                if let Some(notation) = notation {
+                    row = row.push(
+                        Text::new(notation)
+                            .size(14)
+                            .width(Length::Units(val_width))
+                            .font(FONT_PIX_L),
+                    );
+                }
+                if i % per_row == per_row - 1 {
+                    swatches = swatches.push(row);
+                    row = Row::new().spacing(4).align_items(Align::Center);
+                }
+            }
+            swatches = swatches.push(row);
+
+            let mut palette_controls = Column::new().spacing(5).push(swatches);
+
+            if let Some((color, locked)) = selected_entry {
+                let actions = Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(
+                        Text::new(color.to_string())
+                            .width(Length::Units(sub_name_width))
+                            .font(FONT_PIX_L),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.palette_lock_button,
+                            Text::new(if locked { "unlock" } else { "lock" }),
+                        )
+                        .on_press(Event::PaletteLockPressed)
+                        .style(self.theme),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.palette_merge_button,
+                            Text::new(if self.palette_merging {
+                                "cancel"
+                            } else {
+                                "merge"
+                            }),
+                        )
+                        .on_press(Event::PaletteMergePressed)
+                        .style(self.theme),
+                    );
+
+                let red = Row::new()
+                    .spacing(10)
+                    .push(Text::new("red").width(Length::Units(sub_name_width)))
+                    .push(
+                        Slider::new(
+                            &mut self.palette_red_slider,
+                            0..=255,
+                            color.r,
+                            Event::SliderPaletteRedChanged,
+                        )
+                        .on_release(Event::SliderPaletteColorReleased)
+                        .width(Length::Fill)
+                        .style(self.theme),
+                    )
+                    .push(
+                        Text::new(color.r.to_string())
+                            .width(Length::Units(val_width))
+                            .font(FONT_PIX_L),
+                    );
+
+                let green = Row::new()
+                    .spacing(10)
+                    .push(Text::new("green").width(Length::Units(sub_name_width)))
+                    .push(
+                        Slider::new(
+                            &mut self.palette_green_slider,
+                            0..=255,
+                            color.g,
+                            Event::SliderPaletteGreenChanged,
+                        )
+                        .on_release(Event::SliderPaletteColorReleased)
+                        .width(Length::Fill)
+                        .style(self.theme),
+                    )
+                    .push(
+                        Text::new(color.g.to_string())
+                            .width(Length::Units(val_width))
+                            .font(FONT_PIX_L),
+                    );
+
+                let blue = Row::new()
+                    .spacing(10)
+                    .push(Text::new("blue").width(Length::Units(sub_name_width)))
+                    .push(
+                        Slider::new(
+                            &mut self.palette_blue_slider,
+                            0..=255,
+                            color.b,
+                            Event::SliderPaletteBlueChanged,
+                        )
+                        .on_release(Event::SliderPaletteColorReleased)
+                        .width(Length::Fill)
+                        .style(self.theme),
+                    )
+                    .push(
+                        Text::new(color.b.to_string())
+                            .width(Length::Units(val_width))
+                            .font(FONT_PIX_L),
+                    );
+
+                palette_controls = palette_controls.push(actions);
+                if self.palette_merging {
+                    palette_controls =
+                        palette_controls.push(Text::new("pick the color to merge into"));
+                }
+                palette_controls = palette_controls.push(red).push(green).push(blue);
+            }
+
+            palette_controls = palette_controls.push(
+                Row::new().push(
+                    Button::new(
+                        &mut self.palette_reuse_button,
+                        Text::new("reuse from image"),
+                    )
+                    .on_press(Event::PaletteReusePressed)
+                    .style(self.theme),
+                ),
             );
 
-        let mut modulate = Row::new().padding(PADDING).spacing(10).push(
-            Checkbox::new(self.modulate_toggle, "Modulate", Event::ModulateToggled)
+            palette = palette.push(palette_controls);
+        }
+
+        let mut analysis = Row::new().padding(PADDING).spacing(10).push(
+            Checkbox::new(self.analysis_toggle, "Analysis", Event::AnalysisToggled)
                 .width(Length::Units(main_name_width))
                 .spacing(10)
                 .style(self.theme),
         );
 
-        if self.modulate_toggle {
-            modulate = modulate.push(
+        if self.analysis_toggle {
+            let chart = |histogram: &Histogram, channels| {
+                Canvas::new(histogram::Chart {
+                    histogram: histogram.clone(),
+                    channels,
+                    markers: vec![],
+                })
+                .width(Length::Fill)
+                .height(Length::Units(50))
+            };
+
+            let mut swatches = Column::new().spacing(5);
+            for pair in self.result_swatches.chunks(2) {
+                let mut row = Row::new().spacing(10);
+                for swatch in pair {
+                    row = row.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Align::Center)
+                            .width(Length::FillPortion(1))
+                            .push(
+                                Container::new(Space::new(Length::Units(16), Length::Units(16)))
+                                    .style(style::Swatch(swatch.color.to_color())),
+                            )
+                            .push(Text::new(swatch.color.to_string()).font(FONT_PIX_L))
+                            .push(Space::with_width(Length::Fill))
+                            .push(Text::new(swatch.count.to_string()).font(FONT_PIX_L)),
+                    );
+                }
+                if pair.len() == 1 {
+                    row = row.push(Space::with_width(Length::FillPortion(1)));
+                }
+                swatches = swatches.push(row);
+            }
+
+            analysis = analysis.push(
                 Column::new()
-                    .push(modulate_brightness)
-                    .push(modulate_saturation)
-                    .push(modulate_hue),
+                    .spacing(5)
+                    .push(Text::new("source"))
+                    .push(chart(&self.source_histogram, histogram::Channels::Luma))
+                    .push(chart(&self.source_histogram, histogram::Channels::Rgb))
+                    .push(Text::new("result"))
+                    .push(chart(&self.result_histogram, histogram::Channels::Luma))
+                    .push(chart(&self.result_histogram, histogram::Channels::Rgb))
+                    .push(Text::new(format!(
+                        "quantized in {} ms, RMSE {:.2}",
+                        self.quantize_time.as_millis(),
+                        self.quantize_error
+                    )))
+                    .push(Text::new(format!("{} colors", self.result_swatches.len())))
+                    .push(swatches),
             );
         } else {
-            modulate = modulate.push(Space::with_width(Length::Fill))
+            analysis = analysis.push(Space::with_width(Length::Fill));
         }
 
         let controls_length = match self.layout {
@@ -485,12 +2812,39 @@ impl Sandbox for Easel {
             .align_items(Align::Center)
             .width(controls_length)
             .push(header)
+            .push(batch)
+            .push(animation)
+            .push(transform)
+            .push(sheet)
             .push(pixelize)
+            .push(grid)
             .push(kcolors)
-            .push(levels)
-            .push(modulate);
+            .push(quantizer)
+            .push(seed)
+            .push(cluster_space)
+            .push(remap_metric)
+            .push(stack_add)
+            .push(stack)
+            .push(background)
+            .push(alpha)
+            .push(cleanup)
+            .push(outline)
+            .push(pattern)
+            .push(constraint)
+            .push(depth)
+            .push(tilemap)
+            .push(palette)
+            .push(analysis);
+
+        let picker = if self.crop_editing {
+            Picker::new(&mut self.picker, self.crop_handle.clone()).on_drag(Event::PreviewDragged)
+        } else if self.background_picking {
+            Picker::new(&mut self.picker, self.img_handle.clone()).on_pick(Event::PreviewPicked)
+        } else {
+            Picker::new(&mut self.picker, self.img_handle.clone())
+        };
 
-        let image = Container::new(Image::new(self.img_handle.clone()))
+        let image = Container::new(picker)
             .padding(PADDING)
             .align_x(Align::Center)
             .align_y(Align::Center)
@@ -524,65 +2878,383 @@ impl Sandbox for Easel {
 
 impl Easel {
     fn make_img(&mut self) {
-        let Easel {
-            src_path,
-            img_handle,
-            pixelize,
-            kcolors,
-            level_toggle,
-            level_black,
-            level_white,
-            modulate_toggle,
-            modulate_brightness,
-            modulate_saturation,
-            modulate_hue,
-            saved,
-            ..
-        } = self;
-
-        let src_path = match src_path.as_ref().map(PathBuf::as_path) {
+        let src_path = match self.src_path.clone() {
             Some(src_path) => src_path,
             None => return,
         };
 
-        let wand = MagickWand::new();
+        let locked = self.locked_colors();
+        let render = if self.batch.len() > 1 {
+            self.batch_palette(&locked).and_then(|palette| {
+                pipeline::remap(&src_path, &self.params, &palette, locked.len())
+            })
+        } else {
+            pipeline::render(&src_path, &self.params, &locked)
+        };
+        let render = match render {
+            Ok(render) => render,
+            Err(_) => return,
+        };
+
+        self.frame = render.frame;
+        self.source_histogram = render.source_histogram;
+        self.stage_histograms = render.stage_histograms;
+        self.results = render.results;
+        self.delays = render.delays;
+        self.sheet = render.sheet;
+        self.quantize_time = render.quantize_time;
+        self.quantize_error = render.quantize_error;
+        // A shared palette is remapped to as a locked one, only the colors
+        // locked beforehand stay so
+        let batch = self.batch.len() > 1;
+        self.palette_entries = render
+            .palette
+            .into_iter()
+            .zip(render.locked)
+            .enumerate()
+            .map(|(i, (color, is_locked))| {
+                PaletteEntry::new(color, is_locked && (!batch || i < locked.len()))
+            })
+            .collect();
+        self.palette_selected = None;
+        self.palette_merging = false;
+
+        self.render_result();
+    }
+
+    /// The palette shared by the batch, clustered anew only when the locked
+    /// colors or a setting up to the quantization changed.
+    fn batch_palette(&mut self, locked: &[Rgb]) -> Result<Vec<Rgb>, Box<dyn Error>> {
+        let key = pipeline::palette_key(&self.params);
+        if let Some((cached_key, cached_locked, palette)) = &self.batch_palette {
+            if cached_locked == locked && *cached_key == key {
+                return Ok(palette.clone());
+            }
+        }
+
+        let palette = pipeline::shared_palette(&self.batch, &self.params, locked)?;
+        self.batch_palette = Some((key, locked.to_vec(), palette.clone()));
+        Ok(palette)
+    }
 
-        wand.read_image(src_path.to_string_lossy().as_ref()).ok();
-        let width = wand.get_image_width();
-        let height = wand.get_image_height();
+    fn locked_colors(&self) -> Vec<Rgb> {
+        self.palette_entries
+            .iter()
+            .filter(|entry| entry.locked)
+            .map(|entry| entry.color)
+            .collect()
+    }
 
-        let downsize = (100.0 - *pixelize as f64) / 100.0;
-        let width_ds = ((width as f64) * downsize).round() as usize;
-        let height_ds = ((height as f64) * downsize).round() as usize;
-        wand.resize_image(width_ds, height_ds, magick::FilterType_UndefinedFilter);
+    /// Keeps a set of widgets for each stage of the adjustment stack.
+    fn sync_stage_widgets(&mut self) {
+        self.stage_widgets
+            .resize_with(self.params.stack.len(), StageWidgets::default);
+    }
 
-        if *level_toggle {
-            wand.level_image(
-                *level_black as f64 / 100.0,
-                1.0,
-                *level_white as f64 / 100.0,
-            )
-            .ok();
+    fn adjustment_mut(&mut self, i: usize) -> Option<&mut Adjustment> {
+        self.params
+            .stack
+            .get_mut(i)
+            .map(|stage| &mut stage.adjustment)
+    }
+
+    /// Encodes the indexed frames with the current palette, upscaled back to
+    /// the source size.
+    fn render_result(&mut self) {
+        let colors = self.colors();
+        let pixels = self
+            .results
+            .iter()
+            .flat_map(|result| result.to_opaque_rgb(&colors))
+            .collect::<Vec<_>>();
+
+        self.result_histogram = Histogram::from_rgb(&pixels);
+        self.result_swatches = palette::usage(&pixels);
+        self.count_tiles();
+
+        if let Some(grid) = self.sheet {
+            let padding = self.params.sheet_padding as usize;
+            let img_bytes =
+                match pipeline::encode_sheet(&self.results, &colors, self.frame, grid, padding) {
+                    Ok(img_bytes) => img_bytes,
+                    Err(_) => return,
+                };
+            let transparent =
+                padding > 0 || self.results.iter().any(|result| result.opaque.is_some());
+            let preview_bytes = if self.alpha_checkerboard && transparent {
+                pipeline::preview_sheet(&self.results, &colors, self.frame, grid, padding).ok()
+            } else {
+                None
+            };
+
+            self.img_handle =
+                ImageHandle::from_memory(preview_bytes.unwrap_or_else(|| img_bytes.clone()));
+            self.img_bytes = img_bytes;
+            self.frame_handles = vec![self.img_handle.clone()];
+            self.frame_index = 0;
+            self.saved = false;
+            return;
         }
 
-        if *modulate_toggle {
-            wand.modulate_image(
-                *modulate_brightness as f64,
-                *modulate_saturation as f64,
-                *modulate_hue as f64,
-            )
-            .ok();
+        let mut frame_handles = Vec::with_capacity(self.results.len());
+        for (i, result) in self.results.iter().enumerate() {
+            let img_bytes = match pipeline::encode(result, &colors, self.frame) {
+                Ok(img_bytes) => img_bytes,
+                Err(_) => return,
+            };
+            let preview_bytes = if self.alpha_checkerboard && result.opaque.is_some() {
+                pipeline::preview(result, &colors, self.frame).ok()
+            } else {
+                None
+            };
+
+            frame_handles.push(ImageHandle::from_memory(
+                preview_bytes.unwrap_or_else(|| img_bytes.clone()),
+            ));
+            if i == 0 {
+                self.img_bytes = img_bytes;
+            }
+        }
+
+        self.frame_handles = frame_handles;
+        if self.frame_index >= self.frame_handles.len() {
+            self.frame_index = 0;
+        }
+        if let Some(handle) = self.frame_handles.get(self.frame_index) {
+            self.img_handle = handle.clone();
+        }
+        self.saved = false;
+    }
+
+    /// Writes the result to `save_file`, animations in the chosen format and
+    /// sprite sheets along with their atlas, as well as its tilemap.
+    ///
+    /// The other images of a batch are remapped to the palette it shares and
+    /// written next to it, named after their source.
+    fn export(&self, save_file: &Path) -> Result<(), Box<dyn Error>> {
+        self.export_source(save_file)?;
+        if self.params.pattern_toggle {
+            if let Some(result) = self.results.get(self.frame_index) {
+                let chart = pattern::chart(result, &self.colors(), self.params.pattern_brand);
+                fs::write(pattern::path(save_file), chart)?;
+            }
+        }
+        if let Some(tilemap) = self.tilemap() {
+            self.export_tilemap(save_file, &tilemap)?;
+        }
+
+        let (palette, final_colors) = match &self.batch_palette {
+            Some((_, locked, palette)) => (palette.clone(), locked.len()),
+            None if self.batch.len() > 1 => {
+                let locked = self.locked_colors();
+                let palette = pipeline::shared_palette(&self.batch, &self.params, &locked)?;
+                (palette, locked.len())
+            }
+            None => (vec![], 0),
+        };
+        let padding = self.params.sheet_padding as usize;
+        let format = match self.export_format {
+            Format::Frames => Format::Apng,
+            format => format,
+        };
+        for src_path in self
+            .batch
+            .iter()
+            .filter(|&path| Some(path) != self.src_path.as_ref())
+        {
+            let render = pipeline::remap(src_path, &self.params, &palette, final_colors)?;
+            let stem = save_file.file_stem().unwrap_or_default().to_string_lossy();
+            let src_stem = src_path.file_stem().unwrap_or_default().to_string_lossy();
+            let mut output = save_file.with_file_name(format!("{}_{}", stem, src_stem));
+            if let Some(extension) = save_file.extension() {
+                output.set_extension(extension);
+            }
+
+            if svg::has_extension(&output) {
+                let svg = pipeline::encode_svg(
+                    &render.results,
+                    &render.palette,
+                    render.frame,
+                    render.sheet,
+                    padding,
+                );
+                fs::write(&output, svg)?;
+            } else if let Some(style) = Style::from_extension(&output, self.ansi_style) {
+                let text = terminal::encode(&render.results[0], &render.palette, style);
+                fs::write(&output, text)?;
+            } else {
+                fs::write(&output, pipeline::encode_render(&render, format, padding)?)?;
+            }
+            if let Some(grid) = render.sheet {
+                self.export_atlas(&output, grid, render.frame)?;
+            }
+            if self.params.pattern_toggle {
+                let chart = pattern::chart(
+                    &render.results[0],
+                    &render.palette,
+                    self.params.pattern_brand,
+                );
+                fs::write(pattern::path(&output), chart)?;
+            }
+            if self.tilemap_toggle {
+                let tilemap = Tilemap::new(
+                    &render.results[0],
+                    self.tilemap_size as usize,
+                    self.tilemap_flips,
+                );
+                self.export_tilemap(&output, &tilemap)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn export_source(&self, save_file: &Path) -> Result<(), Box<dyn Error>> {
+        // Animations are written as the frame being shown
+        if let Some(style) = Style::from_extension(save_file, self.ansi_style) {
+            if let Some(result) = self.results.get(self.frame_index) {
+                fs::write(save_file, terminal::encode(result, &self.colors(), style))?;
+            }
+            return Ok(());
+        }
+        if svg::has_extension(save_file) {
+            let results = match self.sheet {
+                Some(_) => &self.results[..],
+                None => &self.results[self.frame_index.min(self.results.len())..],
+            };
+            let svg = pipeline::encode_svg(
+                results,
+                &self.colors(),
+                self.frame,
+                self.sheet,
+                self.params.sheet_padding as usize,
+            );
+            fs::write(save_file, svg)?;
+            if let Some(grid) = self.sheet {
+                self.export_atlas(save_file, grid, self.frame)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(grid) = self.sheet {
+            fs::write(save_file, &self.img_bytes)?;
+            return self.export_atlas(save_file, grid, self.frame);
+        }
+
+        if !self.is_animated() {
+            fs::write(save_file, &self.img_bytes)?;
+            return Ok(());
+        }
+
+        let colors = self.colors();
+        match self.export_format {
+            Format::Frames => {
+                for (i, result) in self.results.iter().enumerate() {
+                    let png = pipeline::encode(result, &colors, self.frame)?;
+                    fs::write(animation::numbered(save_file, i), png)?;
+                }
+            }
+            format => {
+                let bytes = pipeline::encode_animation(
+                    &self.results,
+                    &self.delays,
+                    &colors,
+                    self.frame,
+                    format,
+                )?;
+                fs::write(save_file, bytes)?;
+            }
         }
 
-        wand.kmeans(*kcolors as usize, 100, 0.01).ok();
+        Ok(())
+    }
+
+    /// Writes the atlas of the sheet saved as `save_file` next to it.
+    fn export_atlas(
+        &self,
+        save_file: &Path,
+        grid: Grid,
+        frame: Frame,
+    ) -> Result<(), Box<dyn Error>> {
+        let image = save_file.file_name().unwrap_or_default().to_string_lossy();
+        let atlas = sheet::atlas(
+            self.sheet_atlas,
+            &image,
+            grid,
+            frame.src_size,
+            self.params.sheet_padding as usize,
+        );
+        fs::write(
+            save_file.with_extension(self.sheet_atlas.extension()),
+            atlas,
+        )?;
+        Ok(())
+    }
+
+    /// Writes the tileset of `tilemap` and its map next to `save_file`.
+    fn export_tilemap(&self, save_file: &Path, tilemap: &Tilemap) -> Result<(), Box<dyn Error>> {
+        let (tileset_file, map_file) = tilemap::paths(save_file, self.tilemap_format);
+        fs::write(
+            &tileset_file,
+            pipeline::encode_tileset(tilemap, &self.colors())?,
+        )?;
+        let tileset = tileset_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        fs::write(map_file, tilemap.map(self.tilemap_format, &tileset))?;
+        Ok(())
+    }
+
+    /// Cuts the first result into tiles, when exporting a tilemap.
+    fn tilemap(&self) -> Option<Tilemap> {
+        if !self.tilemap_toggle {
+            return None;
+        }
+        let result = self.results.first()?;
+        Some(Tilemap::new(
+            result,
+            self.tilemap_size as usize,
+            self.tilemap_flips,
+        ))
+    }
+
+    fn count_tiles(&mut self) {
+        self.tilemap_tiles = self.tilemap().map_or(0, |tilemap| tilemap.tiles.len());
+    }
+
+    /// Whether the results are the frames of an animation, rather than a
+    /// still image or the cells of a sheet.
+    fn is_animated(&self) -> bool {
+        self.sheet.is_none() && self.results.len() > 1
+    }
+
+    fn colors(&self) -> Vec<Rgb> {
+        self.palette_entries
+            .iter()
+            .map(|entry| entry.color)
+            .collect()
+    }
 
-        wand.resize_image(width, height, magick::FilterType_PointFilter);
+    /// Shows the whole source with the crop rectangle while it is edited.
+    fn make_crop_preview(&mut self) {
+        if !self.crop_editing {
+            return;
+        }
 
-        if let Ok(img_bytes) = wand.write_image_blob("png") {
-            *img_handle = ImageHandle::from_memory(img_bytes);
-            *saved = false;
+        let crop_preview = self
+            .src_path
+            .as_ref()
+            .and_then(|src_path| pipeline::crop_preview(src_path, &self.params).ok());
+        if let Some(crop_preview) = crop_preview {
+            self.crop_handle = ImageHandle::from_memory(crop_preview);
         }
     }
+
+    fn selected_entry_mut(&mut self) -> Option<&mut PaletteEntry> {
+        let selected = self.palette_selected?;
+        self.palette_entries.get_mut(selected)
+    }
 }
 
 fn icon(unicode: char, size: u16) -> Text {
@@ -626,19 +3298,121 @@ fn theme_icon(theme: &style::Theme) -> Text {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = env::args().collect::<Vec<_>>();
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
 
-    if args.len() >= 2 && (args[1] == "-V" || args[1] == "--version") {
+    if args.help {
+        print!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    if args.version {
         let version = option_env!("CARGO_PKG_VERSION").unwrap_or_else(|| ".");
         println!("v{}", version);
         return Ok(());
     }
 
-    INIT_IMAGE_MAGICK.call_once(|| {
-        magick_wand_genesis();
-    });
+    let mut params = Params::default();
+    if let Some(seed) = args.seed {
+        params.seed = seed;
+    }
+    if let Some(quantizer) = args.quantizer {
+        params.quantizer = quantizer;
+    }
+    if let Some(pattern_brand) = args.pattern {
+        params.pattern_toggle = true;
+        params.pattern_brand = pattern_brand;
+    }
+    if let Some(constraint_hardware) = args.hardware {
+        params.constraint_toggle = true;
+        params.constraint_hardware = constraint_hardware;
+    }
+    if let Some(depth_system) = args.depth {
+        params.depth_toggle = true;
+        params.depth_system = depth_system;
+    }
+    if let Some(cell_size) = args.sheet {
+        params.sheet_toggle = true;
+        params.sheet_slicing = Slicing::Size;
+        params.sheet_cell_size = cell_size;
+    }
+
+    let headless = args.output.is_some() || args.print.is_some();
+    if let Some(src_path) = args.src_path.as_ref().filter(|_| headless) {
+        let render = match &args.palette_from {
+            Some(palette_from) => {
+                let palette = pipeline::shared_palette(&[palette_from.clone()], &params, &[])?;
+                pipeline::remap(src_path, &params, &palette, 0)?
+            }
+            None => pipeline::render(src_path, &params, &[])?,
+        };
+
+        if let Some(style) = args.print {
+            print!(
+                "{}",
+                terminal::encode(&render.results[0], &render.palette, style)
+            );
+        }
+
+        let output = match &args.output {
+            Some(output) => output,
+            None => return Ok(()),
+        };
+        let format = match output.extension() {
+            Some(ext) if ext.to_string_lossy().eq_ignore_ascii_case("gif") => Format::Gif,
+            _ => Format::Apng,
+        };
+        if svg::has_extension(output) {
+            let svg = pipeline::encode_svg(
+                &render.results,
+                &render.palette,
+                render.frame,
+                render.sheet,
+                0,
+            );
+            fs::write(output, svg)?;
+        } else if let Some(style) = Style::from_extension(output, args.ansi.unwrap_or_default()) {
+            let text = terminal::encode(&render.results[0], &render.palette, style);
+            fs::write(output, text)?;
+        } else {
+            fs::write(output, pipeline::encode_render(&render, format, 0)?)?;
+        }
+        if params.pattern_toggle {
+            let chart = pattern::chart(&render.results[0], &render.palette, params.pattern_brand);
+            fs::write(pattern::path(output), chart)?;
+        }
+        if let Some(grid) = render.sheet {
+            let image = output.file_name().unwrap_or_default().to_string_lossy();
+            let atlas = sheet::atlas(Atlas::Json, &image, grid, render.frame.src_size, 0);
+            fs::write(output.with_extension(Atlas::Json.extension()), atlas)?;
+        }
+        if let Some(size) = args.tiles {
+            let tilemap = Tilemap::new(&render.results[0], size as usize, args.tile_flips);
+            let tile_format = args.tile_format.unwrap_or_default();
+            let (tileset_file, map_file) = tilemap::paths(output, tile_format);
+            fs::write(
+                &tileset_file,
+                pipeline::encode_tileset(&tilemap, &render.palette)?,
+            )?;
+            let tileset = tileset_file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            fs::write(map_file, tilemap.map(tile_format, &tileset))?;
+            eprintln!("{} unique tiles", tilemap.tiles.len());
+        }
+        return Ok(());
+    }
 
-    let mut settings = Settings::default();
+    let mut settings = Settings::with_flags(Flags {
+        params,
+        src_path: args.src_path,
+    });
     settings.default_text_size = 18;
     if let Font::External { bytes, .. } = FONT_PIXEL {
         settings.default_font = Some(bytes);
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::color::{ColorSpace, Rgb};
+use crate::palette::Indexed;
+
+/// Where the outline is drawn relative to the opaque regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// On the transparent pixels bordering the regions, growing them.
+    Outer,
+    /// On the edge pixels of the regions themselves.
+    Inner,
+}
+
+impl Side {
+    pub const ALL: [Side; 2] = [Side::Outer, Side::Inner];
+}
+
+impl Default for Side {
+    fn default() -> Self {
+        Self::Outer
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Outer => "outer",
+                Self::Inner => "inner",
+            }
+        )
+    }
+}
+
+/// Which neighbors make two pixels adjacent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    pub const ALL: [Connectivity; 2] = [Connectivity::Four, Connectivity::Eight];
+
+    fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Self::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Self::Eight => &[
+                (0, -1),
+                (-1, 0),
+                (1, 0),
+                (0, 1),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Self::Four
+    }
+}
+
+impl fmt::Display for Connectivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Four => "4-connected",
+                Self::Eight => "8-connected",
+            }
+        )
+    }
+}
+
+/// Draws a 1 pixel outline around the opaque regions of `result`, in `color`
+/// or, without one, in a darkened shade of the adjacent region color.
+///
+/// The outline colors missing from `palette` are appended to it.
+pub fn apply(
+    result: &mut Indexed,
+    palette: &mut Vec<Rgb>,
+    side: Side,
+    connectivity: Connectivity,
+    color: Option<Rgb>,
+) {
+    let (width, height) = (result.width, result.height);
+    let opaque = (0..width * height)
+        .map(|p| result.is_opaque(p))
+        .collect::<Vec<_>>();
+
+    // Out of bounds pixels count as transparent, so that regions touching
+    // the image edges get an inner outline there
+    let neighbors = |p: usize| {
+        let (x, y) = ((p % width) as isize, (p / width) as isize);
+        connectivity.offsets().iter().map(move |&(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width as isize || ny >= height as isize {
+                None
+            } else {
+                Some(ny as usize * width + nx as usize)
+            }
+        })
+    };
+
+    let mut outline = vec![];
+    for p in 0..width * height {
+        let adjacent = match side {
+            Side::Outer if !opaque[p] => neighbors(p)
+                .flatten()
+                .find(|&n| opaque[n])
+                .map(|n| result.indices[n]),
+            Side::Inner if opaque[p] => {
+                let edge = neighbors(p).any(|n| match n {
+                    Some(n) => !opaque[n],
+                    None => true,
+                });
+                if edge {
+                    Some(result.indices[p])
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(adjacent) = adjacent {
+            outline.push((p, adjacent));
+        }
+    }
+
+    for (p, adjacent) in outline {
+        let color = color.unwrap_or_else(|| darken(palette[adjacent]));
+        let i = match palette.iter().position(|&c| c == color) {
+            Some(i) => i,
+            None => {
+                palette.push(color);
+                palette.len() - 1
+            }
+        };
+        result.indices[p] = i;
+        if let Some(opaque) = result.opaque.as_mut() {
+            opaque[p] = true;
+        }
+    }
+}
+
+/// Halves the lightness of `color`.
+fn darken(color: Rgb) -> Rgb {
+    let [l, a, b] = ColorSpace::Lab.coords(color);
+    ColorSpace::Lab.rgb([l * 0.5, a, b])
+}
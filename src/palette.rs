@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::color::{self, ColorSpace, Metric, Rgb};
+use crate::quantize;
+
+/// A palette color along with the number of pixels using it.
+#[derive(Debug, Clone)]
+pub struct Swatch {
+    pub color: Rgb,
+    pub count: u32,
+}
+
+/// Counts the colors of packed 8-bit RGB pixels, most used first.
+pub fn usage(pixels: &[u8]) -> Vec<Swatch> {
+    let mut counts = HashMap::new();
+    for px in pixels.chunks_exact(3) {
+        *counts.entry(Rgb::new(px[0], px[1], px[2])).or_insert(0) += 1;
+    }
+
+    let mut swatches = counts
+        .into_iter()
+        .map(|(color, count)| Swatch { color, count })
+        .collect::<Vec<_>>();
+    swatches.sort_by(|a, b| b.count.cmp(&a.count).then(a.color.cmp(&b.color)));
+
+    swatches
+}
+
+/// Index of the palette color closest to `color`.
+pub fn nearest(palette: &[Rgb], color: Rgb, metric: Metric) -> usize {
+    palette
+        .iter()
+        .map(|&c| metric.distance(c, color))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// Maps packed 8-bit RGB pixels to the index of their nearest palette color.
+pub fn assign(pixels: &[u8], palette: &[Rgb], metric: Metric) -> Vec<usize> {
+    let mut cache = HashMap::new();
+    pixels
+        .chunks_exact(3)
+        .map(|px| {
+            let color = Rgb::new(px[0], px[1], px[2]);
+            *cache
+                .entry(color)
+                .or_insert_with(|| nearest(palette, color, metric))
+        })
+        .collect()
+}
+
+/// Runs k-means iterations in `space` over `pixels` starting from `palette`,
+/// leaving the `locked` colors untouched so that only the remaining ones are
+/// optimized.
+pub fn refine(
+    pixels: &[u8],
+    palette: &mut [Rgb],
+    locked: &[bool],
+    space: ColorSpace,
+    iterations: usize,
+) {
+    // Accumulates in a stable order so that the float sums, hence the
+    // resulting palette, are reproducible.
+    let points = quantize::color_counts(pixels)
+        .into_iter()
+        .map(|(color, count)| (space.coords(color), count as f64))
+        .collect::<Vec<_>>();
+
+    let mut centroids = palette
+        .iter()
+        .map(|&color| space.coords(color))
+        .collect::<Vec<_>>();
+
+    for _ in 0..iterations {
+        let mut sums = vec![[0.0f64; 4]; centroids.len()];
+        for (point, n) in &points {
+            let i = centroids
+                .iter()
+                .map(|centroid| color::squared(*centroid, *point))
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            sums[i][0] += point[0] * n;
+            sums[i][1] += point[1] * n;
+            sums[i][2] += point[2] * n;
+            sums[i][3] += n;
+        }
+
+        let mut moved = false;
+        for (i, [x, y, z, n]) in sums.into_iter().enumerate() {
+            if locked[i] || n == 0.0 {
+                continue;
+            }
+            let mean = [x / n, y / n, z / n];
+            moved |= color::squared(mean, centroids[i]) > 1e-9;
+            centroids[i] = mean;
+        }
+
+        if !moved {
+            break;
+        }
+    }
+
+    for (i, centroid) in centroids.into_iter().enumerate() {
+        if !locked[i] {
+            palette[i] = space.rgb(centroid);
+        }
+    }
+}
+
+/// An image stored as indices into a separate palette.
+#[derive(Debug, Clone, Default)]
+pub struct Indexed {
+    pub width: usize,
+    pub height: usize,
+    pub indices: Vec<usize>,
+    /// Which pixels are opaque, `None` when they all are.
+    pub opaque: Option<Vec<bool>>,
+}
+
+impl Indexed {
+    pub fn to_rgb(&self, palette: &[Rgb]) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.indices.len() * 3);
+        for &i in &self.indices {
+            let color = palette[i];
+            pixels.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+        pixels
+    }
+
+    /// Same as `to_rgb` with an alpha channel, transparent pixels being
+    /// fully transparent black.
+    pub fn to_rgba(&self, palette: &[Rgb]) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.indices.len() * 4);
+        for (p, &i) in self.indices.iter().enumerate() {
+            if self.is_opaque(p) {
+                let color = palette[i];
+                pixels.extend_from_slice(&[color.r, color.g, color.b, 255]);
+            } else {
+                pixels.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+        pixels
+    }
+
+    /// Packed 8-bit RGB of the opaque pixels only.
+    pub fn to_opaque_rgb(&self, palette: &[Rgb]) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.indices.len() * 3);
+        for (p, &i) in self.indices.iter().enumerate() {
+            if self.is_opaque(p) {
+                let color = palette[i];
+                pixels.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+        pixels
+    }
+
+    pub fn is_opaque(&self, p: usize) -> bool {
+        match &self.opaque {
+            Some(opaque) => opaque[p],
+            None => true,
+        }
+    }
+
+    /// Repoints the pixels of palette entry `from` to `into`, then drops
+    /// `from` from the index space.
+    pub fn merge(&mut self, from: usize, into: usize) {
+        for i in self.indices.iter_mut() {
+            if *i == from {
+                *i = into;
+            }
+            if *i > from {
+                *i -= 1;
+            }
+        }
+    }
+}
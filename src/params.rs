@@ -0,0 +1,228 @@
+use std::fmt;
+
+use crate::background::Removal;
+use crate::color::{ColorSpace, Metric, Rgb};
+use crate::constraint::{Depth, Hardware};
+use crate::outline::{Connectivity, Side};
+use crate::pattern::Brand;
+use crate::quantize::Quantizer;
+use crate::sheet::Slicing;
+use crate::stack::{self, Stage};
+use crate::transform::{Aspect, Crop, Rotation};
+
+/// Everything that drives the rendering of an image.
+///
+/// Rendering the same source with the same parameters always produces the
+/// same bytes, the `seed` being the only source of randomness.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params {
+    pub rotation: Rotation,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub crop_toggle: bool,
+    pub crop: Crop,
+    pub crop_aspect: Aspect,
+    pub pixelize: u8,
+    pub pixel_aspect: PixelAspect,
+    /// Width and height of the pixels with a custom aspect.
+    pub pixel_aspect_custom: (u8, u8),
+    /// Where the downscale blocks begin, in source pixels.
+    pub grid_x: u16,
+    pub grid_y: u16,
+    pub kcolors: u8,
+    pub seed: u64,
+    pub quantizer: Quantizer,
+    pub cluster_space: ColorSpace,
+    pub remap_metric: Metric,
+    /// Adjustments in the order they run.
+    pub stack: Vec<Stage>,
+    pub alpha_toggle: bool,
+    pub alpha_threshold: u8,
+    pub alpha_exclude: bool,
+    pub background_toggle: bool,
+    pub background_removal: Removal,
+    pub background_key: Rgb,
+    pub background_tolerance: u8,
+    pub cleanup_toggle: bool,
+    /// Largest size of the regions to remove, in pixels.
+    pub cleanup_orphans: u8,
+    pub cleanup_jaggies: bool,
+    /// Number of cleanup passes.
+    pub cleanup_intensity: u8,
+    pub outline_toggle: bool,
+    pub outline_side: Side,
+    pub outline_connectivity: Connectivity,
+    /// Darkens the adjacent colors instead of using `outline_color`.
+    pub outline_auto: bool,
+    pub outline_color: Rgb,
+    /// Slices the source into cells sharing a palette, as a sprite sheet.
+    pub sheet_toggle: bool,
+    pub sheet_slicing: Slicing,
+    /// Width and height of the cells, in source pixels.
+    pub sheet_cell_size: (u16, u16),
+    /// Number of columns and rows of cells.
+    pub sheet_cell_count: (u8, u8),
+    /// Transparent pixels between the cells of the exported sheet.
+    pub sheet_padding: u8,
+    /// Snaps the palette to the colors of a craft brand.
+    pub pattern_toggle: bool,
+    pub pattern_brand: Brand,
+    /// Limits the colors of each area of the result as a retro system does.
+    pub constraint_toggle: bool,
+    pub constraint_hardware: Hardware,
+    /// Snaps the palette to the RGB grid of a retro system.
+    pub depth_toggle: bool,
+    pub depth_system: Depth,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            rotation: Rotation::default(),
+            flip_horizontal: false,
+            flip_vertical: false,
+            crop_toggle: false,
+            crop: Crop::default(),
+            crop_aspect: Aspect::default(),
+            pixelize: 80,
+            pixel_aspect: PixelAspect::default(),
+            pixel_aspect_custom: (1, 1),
+            grid_x: 0,
+            grid_y: 0,
+            kcolors: 32,
+            seed: 0,
+            quantizer: Quantizer::default(),
+            cluster_space: ColorSpace::default(),
+            remap_metric: Metric::default(),
+            stack: stack::initial(),
+            alpha_toggle: false,
+            alpha_threshold: 50,
+            alpha_exclude: true,
+            background_toggle: false,
+            background_removal: Removal::default(),
+            background_key: Rgb::new(255, 255, 255),
+            background_tolerance: 10,
+            cleanup_toggle: false,
+            cleanup_orphans: 2,
+            cleanup_jaggies: true,
+            cleanup_intensity: 1,
+            outline_toggle: false,
+            outline_side: Side::default(),
+            outline_connectivity: Connectivity::default(),
+            outline_auto: true,
+            outline_color: Rgb::new(0, 0, 0),
+            sheet_toggle: false,
+            sheet_slicing: Slicing::default(),
+            sheet_cell_size: (32, 32),
+            sheet_cell_count: (4, 4),
+            sheet_padding: 0,
+            pattern_toggle: false,
+            pattern_brand: Brand::default(),
+            constraint_toggle: false,
+            constraint_hardware: Hardware::default(),
+            depth_toggle: false,
+            depth_system: Depth::default(),
+        }
+    }
+}
+
+/// Width to height ratio of the pixels of the display being targeted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelAspect {
+    Square,
+    /// As C64 multicolor modes.
+    Wide,
+    /// As the NES on a CRT.
+    EightSeven,
+    Custom,
+}
+
+impl PixelAspect {
+    pub const ALL: [PixelAspect; 4] = [
+        PixelAspect::Square,
+        PixelAspect::Wide,
+        PixelAspect::EightSeven,
+        PixelAspect::Custom,
+    ];
+
+    pub fn ratio(self, (width, height): (u8, u8)) -> f64 {
+        match self {
+            Self::Square => 1.0,
+            Self::Wide => 2.0,
+            Self::EightSeven => 8.0 / 7.0,
+            Self::Custom => width.max(1) as f64 / height.max(1) as f64,
+        }
+    }
+}
+
+impl Default for PixelAspect {
+    fn default() -> Self {
+        Self::Square
+    }
+}
+
+impl fmt::Display for PixelAspect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Square => "1:1",
+                Self::Wide => "2:1",
+                Self::EightSeven => "8:7",
+                Self::Custom => "custom",
+            }
+        )
+    }
+}
+
+/// Black and white points in percent, gamma in hundredths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    pub black: u8,
+    pub white: u8,
+    pub gamma: u16,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            black: 0,
+            white: 100,
+            gamma: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Red, Channel::Green, Channel::Blue];
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::Red => 0,
+            Self::Green => 1,
+            Self::Blue => 2,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Red => "red",
+                Self::Green => "green",
+                Self::Blue => "blue",
+            }
+        )
+    }
+}
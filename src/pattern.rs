@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::color::{Metric, Rgb};
+use crate::palette::Indexed;
+
+/// Side of a pattern cell, in SVG units.
+const CELL: usize = 16;
+/// Room left of and above the grid for the row and column numbers.
+const MARGIN: usize = 28;
+/// Height of a shopping list line.
+const LINE: usize = 20;
+
+/// Told apart easily once printed, in the order they are handed out.
+const SYMBOLS: &[char] = &[
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'K', 'L', 'M', 'N', 'P', 'R', 'S', 'T', 'U', 'V', 'W',
+    'X', 'Y', 'Z', '2', '3', '4', '5', '6', '7', '8', '9', '+', '#', '%', '@', '&', '=', '?', '$',
+];
+
+/// A color sold by a craft brand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Supply {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub rgb: [u8; 3],
+}
+
+impl Supply {
+    const fn new(code: &'static str, name: &'static str, rgb: [u8; 3]) -> Self {
+        Self { code, name, rgb }
+    }
+
+    pub fn color(self) -> Rgb {
+        Rgb::new(self.rgb[0], self.rgb[1], self.rgb[2])
+    }
+}
+
+/// Brand whose colors a pattern is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Brand {
+    Perler,
+    Hama,
+    /// Embroidery floss, for cross-stitch.
+    Dmc,
+    /// 1x1 plates, for brick mosaics.
+    Lego,
+}
+
+impl Brand {
+    pub const ALL: [Brand; 4] = [Brand::Perler, Brand::Hama, Brand::Dmc, Brand::Lego];
+
+    pub fn supplies(self) -> &'static [Supply] {
+        match self {
+            Self::Perler => PERLER,
+            Self::Hama => HAMA,
+            Self::Dmc => DMC,
+            Self::Lego => LEGO,
+        }
+    }
+
+    /// What each cell of the pattern stands for.
+    pub fn unit(self) -> &'static str {
+        match self {
+            Self::Perler | Self::Hama => "beads",
+            Self::Dmc => "stitches",
+            Self::Lego => "plates",
+        }
+    }
+
+    /// The brand color closest to `color`, perceptually.
+    pub fn nearest(self, color: Rgb) -> Supply {
+        let supplies = self.supplies();
+        let mut nearest = supplies[0];
+        let mut nearest_distance = f64::MAX;
+        for &supply in supplies {
+            let distance = Metric::Ciede2000.distance(color, supply.color());
+            if distance < nearest_distance {
+                nearest = supply;
+                nearest_distance = distance;
+            }
+        }
+        nearest
+    }
+}
+
+impl Default for Brand {
+    fn default() -> Self {
+        Self::Perler
+    }
+}
+
+impl fmt::Display for Brand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Perler => "Perler",
+                Self::Hama => "Hama",
+                Self::Dmc => "DMC",
+                Self::Lego => "LEGO",
+            }
+        )
+    }
+}
+
+impl FromStr for Brand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perler" => Ok(Self::Perler),
+            "hama" => Ok(Self::Hama),
+            "dmc" => Ok(Self::Dmc),
+            "lego" => Ok(Self::Lego),
+            _ => Err(format!("unknown brand: {}", s)),
+        }
+    }
+}
+
+/// Path of the pattern written along with an export to `path`.
+pub fn path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_pattern.svg", stem))
+}
+
+/// Charts `result` as a printable SVG pattern of `brand` colors: a numbered
+/// grid with one symbol per cell, followed by the count of each color.
+///
+/// Every opaque pixel is one cell, transparent pixels being left empty.
+pub fn chart(result: &Indexed, palette: &[Rgb], brand: Brand) -> String {
+    // Colors mapping to the same supply share a symbol
+    let mut supplies: Vec<(Supply, usize)> = vec![];
+    let mut cells = vec![None; result.indices.len()];
+    let mut by_index = HashMap::new();
+    for (p, &i) in result.indices.iter().enumerate() {
+        if !result.is_opaque(p) {
+            continue;
+        }
+        let k = *by_index.entry(i).or_insert_with(|| {
+            let supply = brand.nearest(palette[i]);
+            match supplies.iter().position(|&(known, _)| known == supply) {
+                Some(k) => k,
+                None => {
+                    supplies.push((supply, 0));
+                    supplies.len() - 1
+                }
+            }
+        });
+        supplies[k].1 += 1;
+        cells[p] = Some(k);
+    }
+
+    let grid_width = result.width * CELL;
+    let grid_height = result.height * CELL;
+    let width = (MARGIN + grid_width + CELL).max(320);
+    let height = MARGIN + grid_height + 2 * LINE + supplies.len() * LINE + CELL;
+
+    // Writing to a `String` cannot fail
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\" text-anchor=\"middle\">",
+        width, height, width, height
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{}\" height=\"{}\" fill=\"#FFFFFF\"/>",
+        width, height
+    );
+
+    // Row and column numbers, counted from 1
+    for x in 0..result.width {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"7\">{}</text>",
+            MARGIN + x * CELL + CELL / 2,
+            MARGIN - 4,
+            x + 1
+        );
+    }
+    for y in 0..result.height {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"7\" text-anchor=\"end\">{}</text>",
+            MARGIN - 4,
+            MARGIN + y * CELL + CELL / 2 + 3,
+            y + 1
+        );
+    }
+
+    for (p, cell) in cells.iter().enumerate() {
+        if let Some(k) = *cell {
+            let (x, y) = (
+                MARGIN + (p % result.width) * CELL,
+                MARGIN + (p / result.width) * CELL,
+            );
+            cell_svg(&mut svg, x, y, supplies[k].0, k);
+        }
+    }
+
+    // Thicker lines every ten cells, as usual for counted patterns
+    for x in 0..=result.width {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"#000000\" stroke-width=\"{}\"/>",
+            MARGIN,
+            MARGIN + grid_height,
+            if x % 10 == 0 { 1.5 } else { 0.5 },
+            x = MARGIN + x * CELL
+        );
+    }
+    for y in 0..=result.height {
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#000000\" stroke-width=\"{}\"/>",
+            MARGIN,
+            MARGIN + grid_width,
+            if y % 10 == 0 { 1.5 } else { 0.5 },
+            y = MARGIN + y * CELL
+        );
+    }
+
+    // Shopping list, most used colors first
+    let total = supplies.iter().map(|&(_, count)| count).sum::<usize>();
+    let mut list_y = MARGIN + grid_height + LINE;
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"start\">{} {}x{}, {} {}</text>",
+        MARGIN,
+        list_y + 11,
+        brand,
+        result.width,
+        result.height,
+        total,
+        brand.unit()
+    );
+    let mut order = (0..supplies.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&k| std::cmp::Reverse(supplies[k].1));
+    for k in order {
+        list_y += LINE;
+        let (supply, count) = supplies[k];
+        cell_svg(&mut svg, MARGIN, list_y, supply, k);
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"start\">{} {}: {}</text>",
+            MARGIN + CELL + 8,
+            list_y + 12,
+            escape(supply.code),
+            escape(supply.name),
+            count
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes a cell of `supply` color at `(x, y)`, marked with symbol `k`.
+fn cell_svg(svg: &mut String, x: usize, y: usize, supply: Supply, k: usize) {
+    let color = supply.color();
+    // Dark symbols on light colors and the other way around
+    let luma = 0.299 * color.r as f64 + 0.587 * color.g as f64 + 0.114 * color.b as f64;
+    let ink = if luma > 128.0 { "#000000" } else { "#FFFFFF" };
+    let _ = writeln!(
+        svg,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+        x, y, CELL, CELL, color
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{}\" y=\"{}\" font-size=\"10\" fill=\"{}\">{}</text>",
+        x + CELL / 2,
+        y + CELL / 2 + 4,
+        ink,
+        escape(&symbol(k))
+    );
+}
+
+/// Symbol of the `k`-th color, numbered once the symbols run out.
+fn symbol(k: usize) -> String {
+    let c = SYMBOLS[k % SYMBOLS.len()];
+    match k / SYMBOLS.len() {
+        0 => c.to_string(),
+        n => format!("{}{}", c, n),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+const PERLER: &[Supply] = &[
+    Supply::new("P01", "White", [241, 241, 241]),
+    Supply::new("P02", "Cream", [224, 222, 169]),
+    Supply::new("P03", "Yellow", [236, 216, 0]),
+    Supply::new("P04", "Orange", [237, 97, 32]),
+    Supply::new("P05", "Red", [191, 46, 64]),
+    Supply::new("P06", "Bubblegum", [222, 132, 185]),
+    Supply::new("P07", "Purple", [85, 81, 148]),
+    Supply::new("P08", "Dark Blue", [43, 63, 135]),
+    Supply::new("P09", "Light Blue", [51, 112, 192]),
+    Supply::new("P10", "Dark Green", [28, 117, 62]),
+    Supply::new("P11", "Light Green", [86, 186, 159]),
+    Supply::new("P12", "Brown", [81, 57, 49]),
+    Supply::new("P17", "Grey", [138, 141, 145]),
+    Supply::new("P18", "Black", [46, 47, 50]),
+    Supply::new("P20", "Rust", [140, 55, 44]),
+    Supply::new("P21", "Light Brown", [129, 93, 52]),
+    Supply::new("P33", "Peach", [238, 186, 178]),
+    Supply::new("P35", "Tan", [207, 168, 137]),
+    Supply::new("P38", "Magenta", [242, 44, 145]),
+    Supply::new("P52", "Pastel Blue", [88, 160, 215]),
+    Supply::new("P53", "Pastel Green", [118, 200, 130]),
+    Supply::new("P54", "Pastel Lavender", [139, 114, 193]),
+    Supply::new("P56", "Pastel Yellow", [248, 238, 121]),
+    Supply::new("P57", "Cheddar", [241, 170, 12]),
+    Supply::new("P58", "Toothpaste", [147, 200, 212]),
+    Supply::new("P59", "Hot Coral", [255, 59, 82]),
+    Supply::new("P60", "Plum", [162, 75, 156]),
+    Supply::new("P61", "Kiwi Lime", [94, 200, 52]),
+    Supply::new("P62", "Turquoise", [0, 150, 178]),
+    Supply::new("P63", "Blush", [255, 140, 160]),
+    Supply::new("P79", "Light Pink", [246, 179, 221]),
+    Supply::new("P80", "Bright Green", [77, 183, 73]),
+    Supply::new("P83", "Pink", [228, 72, 146]),
+    Supply::new("P90", "Butterscotch", [209, 133, 38]),
+    Supply::new("P92", "Dark Grey", [77, 81, 86]),
+    Supply::new("P93", "Blueberry Cream", [130, 152, 212]),
+    Supply::new("P96", "Cranapple", [128, 35, 56]),
+];
+
+const HAMA: &[Supply] = &[
+    Supply::new("H01", "White", [236, 237, 237]),
+    Supply::new("H02", "Cream", [240, 232, 185]),
+    Supply::new("H03", "Yellow", [240, 185, 1]),
+    Supply::new("H04", "Orange", [230, 79, 39]),
+    Supply::new("H05", "Red", [182, 49, 54]),
+    Supply::new("H06", "Pink", [225, 136, 159]),
+    Supply::new("H07", "Purple", [105, 74, 130]),
+    Supply::new("H08", "Blue", [44, 70, 144]),
+    Supply::new("H09", "Light Blue", [48, 92, 176]),
+    Supply::new("H10", "Green", [37, 104, 71]),
+    Supply::new("H11", "Light Green", [73, 174, 137]),
+    Supply::new("H12", "Brown", [83, 65, 55]),
+    Supply::new("H17", "Grey", [131, 136, 138]),
+    Supply::new("H18", "Black", [46, 47, 49]),
+    Supply::new("H20", "Reddish Brown", [127, 51, 42]),
+    Supply::new("H21", "Light Brown", [165, 105, 63]),
+    Supply::new("H22", "Dark Red", [160, 50, 54]),
+    Supply::new("H26", "Flesh", [222, 150, 135]),
+    Supply::new("H27", "Beige", [222, 180, 139]),
+    Supply::new("H28", "Dark Green", [54, 63, 56]),
+    Supply::new("H29", "Claret", [185, 57, 94]),
+    Supply::new("H30", "Burgundy", [104, 36, 41]),
+    Supply::new("H31", "Turquoise", [108, 159, 178]),
+    Supply::new("H32", "Fuchsia", [255, 58, 136]),
+    Supply::new("H33", "Cherry", [243, 89, 103]),
+    Supply::new("H43", "Pastel Yellow", [249, 245, 116]),
+    Supply::new("H44", "Pastel Red", [238, 114, 110]),
+    Supply::new("H45", "Pastel Purple", [135, 117, 183]),
+    Supply::new("H46", "Pastel Blue", [116, 174, 229]),
+    Supply::new("H47", "Pastel Green", [141, 207, 142]),
+    Supply::new("H48", "Pastel Pink", [218, 132, 184]),
+    Supply::new("H49", "Azure", [62, 137, 194]),
+    Supply::new("H60", "Teddy Bear Brown", [200, 132, 58]),
+    Supply::new("H70", "Light Grey", [170, 176, 177]),
+    Supply::new("H71", "Dark Grey", [75, 80, 82]),
+];
+
+const DMC: &[Supply] = &[
+    Supply::new("B5200", "Snow White", [255, 255, 255]),
+    Supply::new("3865", "Winter White", [249, 247, 241]),
+    Supply::new("310", "Black", [0, 0, 0]),
+    Supply::new("413", "Pewter Gray Dark", [86, 86, 86]),
+    Supply::new("414", "Steel Gray Dark", [140, 140, 140]),
+    Supply::new("415", "Pearl Gray", [211, 211, 214]),
+    Supply::new("321", "Red", [199, 43, 59]),
+    Supply::new("666", "Bright Red", [227, 29, 66]),
+    Supply::new("815", "Garnet Medium", [135, 7, 31]),
+    Supply::new("3326", "Rose Light", [251, 173, 180]),
+    Supply::new("605", "Cranberry Very Light", [255, 192, 205]),
+    Supply::new("600", "Cranberry Very Dark", [205, 47, 99]),
+    Supply::new("208", "Lavender Very Dark", [131, 91, 139]),
+    Supply::new("554", "Violet Light", [219, 179, 203]),
+    Supply::new("550", "Violet Very Dark", [92, 24, 78]),
+    Supply::new("820", "Royal Blue Very Dark", [14, 54, 92]),
+    Supply::new("797", "Royal Blue", [19, 71, 125]),
+    Supply::new("799", "Delft Blue Medium", [116, 142, 182]),
+    Supply::new("996", "Electric Blue Medium", [48, 194, 236]),
+    Supply::new("3766", "Peacock Blue Light", [153, 207, 217]),
+    Supply::new("699", "Green", [5, 101, 23]),
+    Supply::new("700", "Green Bright", [7, 115, 27]),
+    Supply::new("702", "Kelly Green", [71, 167, 47]),
+    Supply::new("704", "Chartreuse Bright", [158, 207, 52]),
+    Supply::new("3348", "Yellow Green Light", [204, 217, 177]),
+    Supply::new("307", "Lemon", [253, 237, 84]),
+    Supply::new("725", "Topaz Medium Light", [255, 200, 64]),
+    Supply::new("740", "Tangerine", [255, 139, 0]),
+    Supply::new("946", "Burnt Orange Medium", [235, 99, 7]),
+    Supply::new("945", "Tawny", [251, 213, 187]),
+    Supply::new("738", "Tan Very Light", [236, 204, 158]),
+    Supply::new("434", "Brown Light", [152, 94, 51]),
+    Supply::new("801", "Coffee Brown Dark", [101, 57, 25]),
+    Supply::new("3371", "Black Brown", [30, 17, 8]),
+];
+
+const LEGO: &[Supply] = &[
+    Supply::new("1", "White", [255, 255, 255]),
+    Supply::new("26", "Black", [5, 19, 29]),
+    Supply::new("194", "Light Bluish Gray", [160, 165, 169]),
+    Supply::new("199", "Dark Bluish Gray", [108, 110, 104]),
+    Supply::new("21", "Red", [201, 26, 9]),
+    Supply::new("154", "Dark Red", [114, 14, 15]),
+    Supply::new("23", "Blue", [0, 85, 191]),
+    Supply::new("140", "Dark Blue", [10, 52, 99]),
+    Supply::new("102", "Medium Blue", [90, 147, 219]),
+    Supply::new("321", "Dark Azure", [7, 139, 201]),
+    Supply::new("322", "Medium Azure", [54, 174, 191]),
+    Supply::new("24", "Yellow", [242, 205, 55]),
+    Supply::new("226", "Bright Light Yellow", [255, 240, 58]),
+    Supply::new("106", "Orange", [254, 138, 24]),
+    Supply::new("191", "Bright Light Orange", [248, 187, 61]),
+    Supply::new("28", "Green", [35, 120, 65]),
+    Supply::new("141", "Dark Green", [24, 70, 50]),
+    Supply::new("37", "Bright Green", [75, 159, 74]),
+    Supply::new("119", "Lime", [187, 233, 11]),
+    Supply::new("5", "Tan", [228, 205, 158]),
+    Supply::new("138", "Dark Tan", [149, 138, 115]),
+    Supply::new("192", "Reddish Brown", [88, 42, 18]),
+    Supply::new("308", "Dark Brown", [53, 33, 0]),
+    Supply::new("312", "Medium Nougat", [170, 125, 85]),
+    Supply::new("283", "Light Nougat", [246, 215, 179]),
+    Supply::new("124", "Magenta", [146, 57, 120]),
+    Supply::new("221", "Dark Pink", [200, 112, 160]),
+    Supply::new("222", "Bright Pink", [228, 173, 200]),
+    Supply::new("324", "Medium Lavender", [160, 110, 185]),
+    Supply::new("325", "Lavender", [205, 164, 222]),
+];
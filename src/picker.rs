@@ -0,0 +1,145 @@
+use iced_native::event::{self, Event};
+use iced_native::image::{self, Handle, Image};
+use iced_native::layout::{self, Layout};
+use iced_native::mouse;
+use iced_native::{Clipboard, Element, Hasher, Length, Point, Rectangle, Widget};
+
+/// An image reporting where it gets clicked or dragged over, in fractions of
+/// its size.
+pub struct Picker<'a, Message> {
+    state: &'a mut State,
+    image: Image,
+    on_pick: Option<Box<dyn Fn(f32, f32) -> Message>>,
+    on_drag: Option<OnDrag<Message>>,
+}
+
+type OnDrag<Message> = Box<dyn Fn((f32, f32), (f32, f32)) -> Message>;
+
+#[derive(Debug, Default)]
+pub struct State {
+    drag_start: Option<(f32, f32)>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'a, Message> Picker<'a, Message> {
+    pub fn new(state: &'a mut State, handle: Handle) -> Self {
+        Self {
+            state,
+            image: Image::new(handle),
+            on_pick: None,
+            on_drag: None,
+        }
+    }
+
+    /// Sets the message produced on click.
+    pub fn on_pick(mut self, on_pick: impl Fn(f32, f32) -> Message + 'static) -> Self {
+        self.on_pick = Some(Box::new(on_pick));
+        self
+    }
+
+    /// Sets the message produced when a drag ends, with its start and end.
+    pub fn on_drag(
+        mut self,
+        on_drag: impl Fn((f32, f32), (f32, f32)) -> Message + 'static,
+    ) -> Self {
+        self.on_drag = Some(Box::new(on_drag));
+        self
+    }
+}
+
+impl<'a, Message, Renderer> Widget<Message, Renderer> for Picker<'a, Message>
+where
+    Renderer: image::Renderer,
+{
+    fn width(&self) -> Length {
+        Widget::<Message, Renderer>::width(&self.image)
+    }
+
+    fn height(&self) -> Length {
+        Widget::<Message, Renderer>::height(&self.image)
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        Widget::<Message, Renderer>::layout(&self.image, renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut Renderer,
+        defaults: &Renderer::Defaults,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) -> Renderer::Output {
+        Widget::<Message, Renderer>::draw(
+            &self.image,
+            renderer,
+            defaults,
+            layout,
+            cursor_position,
+            viewport,
+        )
+    }
+
+    fn hash_layout(&self, state: &mut Hasher) {
+        Widget::<Message, Renderer>::hash_layout(&self.image, state)
+    }
+
+    fn on_event(
+        &mut self,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        messages: &mut Vec<Message>,
+        _renderer: &Renderer,
+        _clipboard: Option<&dyn Clipboard>,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        let position = (
+            ((cursor_position.x - bounds.x) / bounds.width).clamp(0.0, 1.0),
+            ((cursor_position.y - bounds.y) / bounds.height).clamp(0.0, 1.0),
+        );
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+                if bounds.contains(cursor_position) =>
+            {
+                if let Some(on_pick) = &self.on_pick {
+                    messages.push(on_pick(position.0, position.1));
+                }
+                if self.on_drag.is_some() {
+                    self.state.drag_start = Some(position);
+                }
+                match (&self.on_pick, &self.on_drag) {
+                    (None, None) => event::Status::Ignored,
+                    _ => event::Status::Captured,
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                match (self.state.drag_start.take(), &self.on_drag) {
+                    (Some(start), Some(on_drag)) => {
+                        messages.push(on_drag(start, position));
+                        event::Status::Captured
+                    }
+                    _ => event::Status::Ignored,
+                }
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+}
+
+impl<'a, Message, Renderer> From<Picker<'a, Message>> for Element<'a, Message, Renderer>
+where
+    Message: 'a,
+    Renderer: image::Renderer,
+{
+    fn from(picker: Picker<'a, Message>) -> Element<'a, Message, Renderer> {
+        Element::new(picker)
+    }
+}
@@ -0,0 +1,964 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::animation::{self, Animation, Format};
+use crate::backend::{Backend, Filter, ImageBackend};
+use crate::background;
+use crate::cleanup;
+use crate::color::{ColorSpace, Metric, Rgb};
+use crate::constraint;
+use crate::histogram::Histogram;
+use crate::outline;
+use crate::palette::{self, Indexed};
+use crate::params::Params;
+use crate::quantize::{self, Rng};
+use crate::sheet::{self, Grid};
+use crate::stack::{self, Kind, Stage};
+use crate::svg;
+use crate::tilemap::Tilemap;
+
+/// Side of the preview checkerboard squares, in pixels.
+const CHECKER_SIZE: usize = 8;
+
+/// Source frames along with how long each one shows.
+type Frames = Vec<(Backend, Duration)>;
+
+/// Outcome of rendering a source image, at the downscaled resolution.
+pub struct Render {
+    pub frame: Frame,
+    pub source_histogram: Histogram,
+    /// Input of each levels stage, empty for the other stages.
+    pub stage_histograms: Vec<Histogram>,
+    /// The frames of the animation, a single one for still images.
+    pub results: Vec<Indexed>,
+    /// How long each frame shows.
+    pub delays: Vec<Duration>,
+    /// How the cells are laid out when slicing a sprite sheet, each cell
+    /// being one of the `results`.
+    pub sheet: Option<Grid>,
+    pub palette: Vec<Rgb>,
+    pub locked: Vec<bool>,
+    pub quantize_time: Duration,
+    pub quantize_error: f64,
+}
+
+/// Where the upscaled result lies relative to the source: it spans `size`,
+/// the source being the `src_size` area at `offset` within it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Frame {
+    pub size: (usize, usize),
+    pub offset: (usize, usize),
+    pub src_size: (usize, usize),
+}
+
+/// Width and height of the downscale blocks, in source pixels.
+pub fn block_size(params: &Params) -> (usize, usize) {
+    let side = 100.0 / (100 - params.pixelize).max(1) as f64;
+    let ratio = params.pixel_aspect.ratio(params.pixel_aspect_custom);
+    (
+        (side * ratio).round().max(1.0) as usize,
+        side.round().max(1.0) as usize,
+    )
+}
+
+/// Pixelizes the image at `src_path`, keeping the `locked` colors in the
+/// resulting palette.
+///
+/// All the frames of an animation share the parameters and a single palette,
+/// so that colors do not flicker from one frame to the next. The cells of a
+/// sprite sheet are rendered the same way, as the frames of an animation.
+pub fn render(src_path: &Path, params: &Params, locked: &[Rgb]) -> Result<Render, Box<dyn Error>> {
+    render_with(src_path, params, locked, locked.len())
+}
+
+/// Same as `render`, only the first `final_colors` of the `locked` colors
+/// being kept as-is by the stages after `Quantize`, the others being
+/// recolored along with the free ones.
+fn render_with(
+    src_path: &Path,
+    params: &Params,
+    locked: &[Rgb],
+    final_colors: usize,
+) -> Result<Render, Box<dyn Error>> {
+    let (sources, sheet, source_histogram) = prepare_cells(src_path, params)?;
+
+    // Histograms describe the first frame
+    let mut stage_histograms = vec![];
+    let mut frame = Frame::default();
+    let mut shrunk = Vec::with_capacity(sources.len());
+    let mut delays = Vec::with_capacity(sources.len());
+    for (k, (image, delay)) in sources.into_iter().enumerate() {
+        let mut histograms = vec![Histogram::default(); params.stack.len()];
+        let (frame_shrunk, frame_frame) = shrink(image, params, &mut histograms)?;
+        if k == 0 {
+            stage_histograms = histograms;
+            frame = frame_frame;
+        }
+        shrunk.push(frame_shrunk);
+        delays.push(delay);
+    }
+
+    let pixels = shrunk
+        .iter()
+        .map(|shrunk| shrunk.image.rgb())
+        .collect::<Result<Vec<_>, _>>()?;
+    let samples = shrunk
+        .iter()
+        .zip(&pixels)
+        .flat_map(|(shrunk, pixels)| opaque_only(pixels, 3, shrunk.exclude(params)))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+
+    let mut colors = locked.to_vec();
+    let mut quantized = None;
+    let free_colors = (params.kcolors as usize).saturating_sub(locked.len());
+    let space = cluster_space(params);
+    if free_colors > 0 && !samples.is_empty() {
+        let mut rng = Rng::new(params.seed);
+        let free = match params
+            .quantizer
+            .palette(&samples, free_colors, space, &mut rng)
+        {
+            Some(free) => free,
+            None => {
+                let seeds =
+                    quantize::kmeans_pp_seeds(&samples, free_colors, ColorSpace::Rgb, &mut rng);
+                let rgb = match shrunk.as_mut_slice() {
+                    [single] if single.exclude(params).is_none() => {
+                        single.image.quantize(&seeds)?;
+                        let rgb = single.image.rgb()?;
+                        quantized = Some(rgb.clone());
+                        rgb
+                    }
+                    // Transparent pixels must not pull the clusters, only
+                    // the opaque ones of every frame are handed to the
+                    // backend
+                    _ => quantize_samples(&samples, &seeds)?,
+                };
+                palette::usage(&rgb)
+                    .into_iter()
+                    .map(|swatch| swatch.color)
+                    .collect()
+            }
+        };
+
+        for color in free {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+    }
+
+    let mut locks = (0..colors.len())
+        .map(|i| i < locked.len())
+        .collect::<Vec<_>>();
+
+    // Only the fully locked palette is a fixed one, the k-means ones are
+    // clustered in their color space.
+    let indices = match quantized {
+        Some(quantized) if locked.is_empty() => {
+            vec![palette::assign(&quantized, &colors, Metric::Rgb)]
+        }
+        _ => {
+            let metric = if free_colors == 0 {
+                params.remap_metric
+            } else if params.quantizer.is_kmeans() {
+                palette::refine(&samples, &mut colors, &locks, space, 30);
+                space.metric()
+            } else {
+                Metric::Rgb
+            };
+            pixels
+                .iter()
+                .map(|pixels| palette::assign(pixels, &colors, metric))
+                .collect()
+        }
+    };
+
+    let quantize_time = start.elapsed();
+    let sample_indices = shrunk
+        .iter()
+        .zip(&indices)
+        .flat_map(|(shrunk, indices)| opaque_only(indices, 1, shrunk.exclude(params)))
+        .collect::<Vec<_>>();
+    let quantize_error = quantize::rmse(&samples, &sample_indices, &colors);
+
+    let mut results = shrunk
+        .into_iter()
+        .zip(indices)
+        .map(|(shrunk, indices)| Indexed {
+            width: shrunk.image.width(),
+            height: shrunk.image.height(),
+            indices,
+            opaque: shrunk.opaque,
+        })
+        .collect::<Vec<_>>();
+
+    // Recoloring the palette, once the pixels are assigned to it, the final
+    // colors being left as-is
+    let quantize = stack::position(&params.stack, Kind::Quantize).unwrap_or(params.stack.len());
+    for (i, stage) in params.stack.iter().enumerate().skip(quantize + 1) {
+        if !stage.enabled || !stage.adjustment.is_pointwise() {
+            continue;
+        }
+        if stage.adjustment.kind() == Kind::Levels {
+            stage_histograms[i] = Histogram::from_rgb(&results[0].to_opaque_rgb(&colors));
+        }
+
+        recolor(&mut colors[final_colors..], stage)?;
+    }
+
+    for result in results.iter_mut() {
+        if params.cleanup_toggle {
+            for _ in 0..params.cleanup_intensity {
+                cleanup::orphans(result, params.cleanup_orphans as usize);
+                if params.cleanup_jaggies {
+                    cleanup::jaggies(result);
+                }
+            }
+        }
+
+        if params.outline_toggle {
+            let color = if params.outline_auto {
+                None
+            } else {
+                Some(params.outline_color)
+            };
+            outline::apply(
+                result,
+                &mut colors,
+                params.outline_side,
+                params.outline_connectivity,
+                color,
+            );
+        }
+    }
+
+    // Crafts are made of the brand colors only, some of the palette colors
+    // merging once snapped
+    if params.pattern_toggle {
+        for color in colors.iter_mut() {
+            *color = params.pattern_brand.nearest(*color).color();
+        }
+        merge_duplicates(&mut results, &mut colors, &mut locks);
+    }
+
+    // Older systems only show colors on a coarser RGB grid
+    if params.depth_toggle {
+        for color in colors.iter_mut() {
+            *color = params.depth_system.snap(*color);
+        }
+        merge_duplicates(&mut results, &mut colors, &mut locks);
+    }
+
+    // Retro systems only show a few colors of their own per area of the
+    // screen, the palette colors left unused being dropped, and some only a
+    // few shades
+    if params.constraint_toggle {
+        if let Some(master) = params.constraint_hardware.master_palette() {
+            for color in colors.iter_mut() {
+                *color = master[palette::nearest(master, *color, params.remap_metric)];
+            }
+            merge_duplicates(&mut results, &mut colors, &mut locks);
+        }
+        for result in results.iter_mut() {
+            constraint::apply(
+                result,
+                &colors,
+                params.constraint_hardware,
+                params.remap_metric,
+            );
+        }
+        for i in (0..colors.len()).rev() {
+            let used = results.iter().any(|result| {
+                (0..result.indices.len()).any(|p| result.is_opaque(p) && result.indices[p] == i)
+            });
+            if !used && colors.len() > 1 {
+                for result in results.iter_mut() {
+                    result.merge(i, 0);
+                }
+                colors.remove(i);
+                if i < locks.len() {
+                    locks.remove(i);
+                }
+            }
+        }
+        if let Some(shades) = params.constraint_hardware.shades() {
+            constraint::shade(&mut colors, shades);
+            merge_duplicates(&mut results, &mut colors, &mut locks);
+        }
+    }
+    locks.resize(colors.len(), false);
+
+    Ok(Render {
+        frame,
+        source_histogram,
+        stage_histograms,
+        results,
+        delays,
+        sheet,
+        palette: colors,
+        locked: locks,
+        quantize_time,
+        quantize_error,
+    })
+}
+
+/// Computes a single palette for all the images at `src_paths`, keeping the
+/// `locked` colors, so that related images look consistent once remapped to
+/// it with `remap`.
+///
+/// The opaque pixels of every image are clustered together. The palette is
+/// the one the pixels are assigned to, before the stages after `Quantize`
+/// recolor it.
+pub fn shared_palette(
+    src_paths: &[PathBuf],
+    params: &Params,
+    locked: &[Rgb],
+) -> Result<Vec<Rgb>, Box<dyn Error>> {
+    // Only the settings of the key may change the palette
+    let params = &palette_key(params);
+
+    let mut samples = vec![];
+    for src_path in src_paths {
+        for (image, _) in prepare_cells(src_path, params)?.0 {
+            let mut histograms = vec![Histogram::default(); params.stack.len()];
+            let (shrunk, _) = shrink(image, params, &mut histograms)?;
+            samples.extend(opaque_only(&shrunk.image.rgb()?, 3, shrunk.exclude(params)));
+        }
+    }
+
+    let mut colors = locked.to_vec();
+    let free_colors = (params.kcolors as usize).saturating_sub(locked.len());
+    let space = cluster_space(params);
+    if free_colors > 0 && !samples.is_empty() {
+        let mut rng = Rng::new(params.seed);
+        let free = match params
+            .quantizer
+            .palette(&samples, free_colors, space, &mut rng)
+        {
+            Some(free) => free,
+            None => {
+                let seeds =
+                    quantize::kmeans_pp_seeds(&samples, free_colors, ColorSpace::Rgb, &mut rng);
+                palette::usage(&quantize_samples(&samples, &seeds)?)
+                    .into_iter()
+                    .map(|swatch| swatch.color)
+                    .collect()
+            }
+        };
+
+        for color in free {
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+
+        if params.quantizer.is_kmeans() {
+            let locks = (0..colors.len())
+                .map(|i| i < locked.len())
+                .collect::<Vec<_>>();
+            palette::refine(&samples, &mut colors, &locks, space, 30);
+        }
+    }
+
+    Ok(colors)
+}
+
+/// The settings `shared_palette` computes a palette from: `params` with the
+/// ones only read past the quantization reset, so that equal keys yield the
+/// same palette.
+pub fn palette_key(params: &Params) -> Params {
+    let defaults = Params::default();
+    let quantize = stack::position(&params.stack, Kind::Quantize).unwrap_or(params.stack.len());
+    Params {
+        remap_metric: defaults.remap_metric,
+        stack: params.stack[..(quantize + 1).min(params.stack.len())].to_vec(),
+        cleanup_toggle: defaults.cleanup_toggle,
+        cleanup_orphans: defaults.cleanup_orphans,
+        cleanup_jaggies: defaults.cleanup_jaggies,
+        cleanup_intensity: defaults.cleanup_intensity,
+        outline_toggle: defaults.outline_toggle,
+        outline_side: defaults.outline_side,
+        outline_connectivity: defaults.outline_connectivity,
+        outline_auto: defaults.outline_auto,
+        outline_color: defaults.outline_color,
+        sheet_padding: defaults.sheet_padding,
+        pattern_toggle: defaults.pattern_toggle,
+        pattern_brand: defaults.pattern_brand,
+        constraint_toggle: defaults.constraint_toggle,
+        constraint_hardware: defaults.constraint_hardware,
+        depth_toggle: defaults.depth_toggle,
+        depth_system: defaults.depth_system,
+        ..params.clone()
+    }
+}
+
+/// Renders the image at `src_path` with exactly the given `palette`, as
+/// computed by `shared_palette`, every color of which ends up locked.
+///
+/// The first `final_colors` are kept as-is, the others being recolored by the
+/// stages after `Quantize` once the pixels are assigned to them.
+pub fn remap(
+    src_path: &Path,
+    params: &Params,
+    palette: &[Rgb],
+    final_colors: usize,
+) -> Result<Render, Box<dyn Error>> {
+    let mut params = params.clone();
+    params.kcolors = palette.len().min(u8::MAX as usize) as u8;
+    let palette = &palette[..params.kcolors as usize];
+    render_with(src_path, &params, palette, final_colors.min(palette.len()))
+}
+
+/// Runs the pointwise stages after `Quantize` over the `colors` of a palette,
+/// as `render` does once the pixels are assigned to them.
+pub fn recolor_palette(colors: &mut [Rgb], params: &Params) -> Result<(), Box<dyn Error>> {
+    let quantize = stack::position(&params.stack, Kind::Quantize).unwrap_or(params.stack.len());
+    for stage in params.stack.iter().skip(quantize + 1) {
+        if stage.enabled && stage.adjustment.is_pointwise() {
+            recolor(colors, stage)?;
+        }
+    }
+    Ok(())
+}
+
+/// Merges the palette colors equal to an earlier one into it.
+fn merge_duplicates(results: &mut [Indexed], colors: &mut Vec<Rgb>, locks: &mut Vec<bool>) {
+    for i in (1..colors.len()).rev() {
+        if let Some(j) = colors[..i].iter().position(|&color| color == colors[i]) {
+            for result in results.iter_mut() {
+                result.merge(i, j);
+            }
+            colors.remove(i);
+            if i < locks.len() {
+                locks.remove(i);
+            }
+        }
+    }
+}
+
+/// Color space the quantizer clusters in, only k-means++ honoring the chosen
+/// one.
+fn cluster_space(params: &Params) -> ColorSpace {
+    if params.quantizer.uses_space() {
+        params.cluster_space
+    } else {
+        ColorSpace::Rgb
+    }
+}
+
+/// Clusters packed 8-bit RGB `samples` around `seeds` with the backend.
+fn quantize_samples(samples: &[u8], seeds: &[Rgb]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut strip = Backend::from_rgb(samples.len() / 3, 1, samples)?;
+    strip.quantize(seeds)?;
+    strip.rgb()
+}
+
+/// Runs a pointwise `stage` over the `colors` of a palette.
+fn recolor(colors: &mut [Rgb], stage: &Stage) -> Result<(), Box<dyn Error>> {
+    let pixels = colors
+        .iter()
+        .flat_map(|color| vec![color.r, color.g, color.b])
+        .collect::<Vec<_>>();
+    if pixels.is_empty() {
+        return Ok(());
+    }
+
+    let mut strip = Backend::from_rgb(pixels.len() / 3, 1, &pixels)?;
+    stage.adjustment.apply(&mut strip)?;
+    for (color, px) in colors.iter_mut().zip(strip.rgb()?.chunks_exact(3)) {
+        *color = Rgb::new(px[0], px[1], px[2]);
+    }
+    Ok(())
+}
+
+/// A source frame at the result size, ready for quantization.
+struct Shrunk {
+    image: Backend,
+    /// Which pixels are opaque, `None` without transparency handling.
+    opaque: Option<Vec<bool>>,
+}
+
+impl Shrunk {
+    /// Pixels to leave out of the palette, when they are transparent.
+    fn exclude(&self, params: &Params) -> Option<&[bool]> {
+        match &self.opaque {
+            Some(opaque) if params.alpha_exclude => Some(opaque.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+/// Removes the background, downscales and adjusts a prepared source frame,
+/// filling the histograms of the levels stages along the way.
+fn shrink(
+    mut image: Backend,
+    params: &Params,
+    stage_histograms: &mut [Histogram],
+) -> Result<(Shrunk, Frame), Box<dyn Error>> {
+    let width = image.width();
+    let height = image.height();
+
+    if params.background_toggle {
+        let mut pixels = image.rgba()?;
+        background::remove(
+            &mut pixels,
+            width,
+            height,
+            params.background_removal,
+            params.background_key,
+            params.background_tolerance,
+        );
+        image = Backend::from_rgba(width, height, &pixels)?;
+    }
+
+    let downscale = stack::position(&params.stack, Kind::Downscale).unwrap_or(0);
+    let quantize = stack::position(&params.stack, Kind::Quantize).unwrap_or(params.stack.len());
+
+    for (i, stage) in params.stack.iter().enumerate().take(downscale) {
+        stage_histograms[i] = adjust(&mut image, stage, None)?;
+    }
+
+    // Padding with the edge pixels so that blocks begin at the grid offsets,
+    // the padded size being a whole number of blocks. Wide pixels make for
+    // wide blocks, the upscale stretching them back
+    let (block_x, block_y) = block_size(params);
+    let left = (block_x - params.grid_x as usize % block_x) % block_x;
+    let top = (block_y - params.grid_y as usize % block_y) % block_y;
+    let blocks = |size: usize, block: usize| (size as f64 / block as f64).ceil() as usize;
+    let padded_width = blocks(left + width, block_x) * block_x;
+    let padded_height = blocks(top + height, block_y) * block_y;
+
+    let pixels = pad(
+        &image.rgba()?,
+        (width, height),
+        (left, top),
+        (padded_width, padded_height),
+    );
+    image = Backend::from_rgba(
+        padded_width / block_x,
+        padded_height / block_y,
+        &block_means(&pixels, padded_width, (block_x, block_y)),
+    )?;
+
+    let frame = Frame {
+        size: (padded_width, padded_height),
+        offset: (left, top),
+        src_size: (width, height),
+    };
+
+    // Binarizing alpha after the downscale drops the half-transparent edges
+    // averaging creates, a removed background always needs it
+    let opaque = if params.alpha_toggle || params.background_toggle {
+        let cutoff = params.alpha_threshold as u32 * 255;
+        let rgba = image.rgba()?;
+        Some(
+            rgba.chunks_exact(4)
+                .map(|px| px[3] as u32 * 100 >= cutoff)
+                .collect::<Vec<_>>(),
+        )
+    } else {
+        None
+    };
+    let exclude = match &opaque {
+        Some(opaque) if params.alpha_exclude => Some(opaque.as_slice()),
+        _ => None,
+    };
+
+    for (i, stage) in params.stack.iter().enumerate().take(quantize) {
+        if i > downscale {
+            stage_histograms[i] = adjust(&mut image, stage, exclude)?;
+        }
+    }
+
+    Ok((Shrunk { image, opaque }, frame))
+}
+
+/// Runs an enabled stage over `image`, returning the histogram of its input
+/// when it is a levels one.
+fn adjust(
+    image: &mut Backend,
+    stage: &Stage,
+    exclude: Option<&[bool]>,
+) -> Result<Histogram, Box<dyn Error>> {
+    if !stage.enabled {
+        return Ok(Histogram::default());
+    }
+
+    let histogram = if stage.adjustment.kind() == Kind::Levels {
+        Histogram::from_rgb(&opaque_only(&image.rgb()?, 3, exclude))
+    } else {
+        Histogram::default()
+    };
+    stage.adjustment.apply(image)?;
+
+    Ok(histogram)
+}
+
+/// Reads every frame of the source, a still image being a single one, then
+/// rotates, flips and, with `crop`, crops them.
+fn prepare_frames(src_path: &Path, params: &Params, crop: bool) -> Result<Frames, Box<dyn Error>> {
+    let frames = match animation::read(src_path)? {
+        Some(Animation {
+            width,
+            height,
+            frames,
+        }) => frames
+            .into_iter()
+            .map(|(pixels, delay)| Ok((Backend::from_rgba(width, height, &pixels)?, delay)))
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?,
+        None => vec![(Backend::read(src_path)?, Duration::default())],
+    };
+
+    frames
+        .into_iter()
+        .map(|(image, delay)| Ok((orient(image, params, crop)?, delay)))
+        .collect()
+}
+
+/// The frames of the source prepared as in `prepare_frames`, or the cells of
+/// its first frame when slicing a sprite sheet, along with the histogram of
+/// that first frame.
+fn prepare_cells(
+    src_path: &Path,
+    params: &Params,
+) -> Result<(Frames, Option<Grid>, Histogram), Box<dyn Error>> {
+    let mut sources = prepare_frames(src_path, params, true)?;
+    let source_histogram = match sources.first() {
+        Some((image, _)) => Histogram::from_rgb(&image.rgb()?),
+        None => return Err("empty image".into()),
+    };
+
+    // Only the first frame of an animated sheet is sliced
+    if !params.sheet_toggle {
+        return Ok((sources, None, source_histogram));
+    }
+    let (image, _) = sources.swap_remove(0);
+    let (width, height) = (image.width(), image.height());
+    let grid = Grid::new(
+        params.sheet_slicing,
+        params.sheet_cell_size,
+        params.sheet_cell_count,
+        (width, height),
+    );
+    let pixels = image.rgba()?;
+    let cells = grid
+        .cells()
+        .map(|rect| {
+            let cell = Backend::from_rgba(rect[2], rect[3], &sheet::cut(&pixels, width, rect))?;
+            Ok((cell, Duration::default()))
+        })
+        .collect::<Result<Frames, Box<dyn Error>>>()?;
+    Ok((cells, Some(grid), source_histogram))
+}
+
+/// First frame of the source, prepared as in `prepare_frames`.
+fn prepare(src_path: &Path, params: &Params, crop: bool) -> Result<Backend, Box<dyn Error>> {
+    prepare_frames(src_path, params, crop)?
+        .into_iter()
+        .next()
+        .map(|(image, _)| image)
+        .ok_or_else(|| "empty image".into())
+}
+
+fn orient(mut image: Backend, params: &Params, crop: bool) -> Result<Backend, Box<dyn Error>> {
+    image.rotate(params.rotation.quarter_turns())?;
+    if params.flip_horizontal {
+        image.flip(true)?;
+    }
+    if params.flip_vertical {
+        image.flip(false)?;
+    }
+
+    if crop && params.crop_toggle {
+        let [x, y, width, height] =
+            params
+                .crop
+                .pixels(params.crop_aspect, image.width(), image.height());
+        image.crop(x, y, width, height)?;
+    }
+
+    Ok(image)
+}
+
+/// Color of the prepared source at `(x, y)`, in fractions of its size.
+pub fn pick(src_path: &Path, params: &Params, (x, y): (f32, f32)) -> Result<Rgb, Box<dyn Error>> {
+    let image = prepare(src_path, params, true)?;
+    let (width, height) = (image.width(), image.height());
+    let x = ((x * width as f32) as usize).min(width.saturating_sub(1));
+    let y = ((y * height as f32) as usize).min(height.saturating_sub(1));
+
+    let pixels = image.rgb()?;
+    let px = pixels
+        .get((y * width + x) * 3..(y * width + x) * 3 + 3)
+        .ok_or("empty image")?;
+    Ok(Rgb::new(px[0], px[1], px[2]))
+}
+
+/// Encodes the uncropped source as a PNG, dimmed outside of the crop
+/// rectangle.
+pub fn crop_preview(src_path: &Path, params: &Params) -> Result<Vec<u8>, Box<dyn Error>> {
+    let image = prepare(src_path, params, false)?;
+    let (width, height) = (image.width(), image.height());
+    let [x, y, w, h] = params.crop.pixels(params.crop_aspect, width, height);
+
+    let mut pixels = image.rgba()?;
+    for (p, px) in pixels.chunks_exact_mut(4).enumerate() {
+        let (px_x, px_y) = (p % width, p / width);
+        if px_x < x || px_x >= x + w || px_y < y || px_y >= y + h {
+            for c in &mut px[..3] {
+                *c /= 3;
+            }
+        }
+    }
+
+    Backend::from_rgba(width, height, &pixels)?.encode_png()
+}
+
+/// Encodes the indexed result as a PNG, upscaled back to the source size.
+pub fn encode(result: &Indexed, palette: &[Rgb], frame: Frame) -> Result<Vec<u8>, Box<dyn Error>> {
+    upscale(result, palette, frame)?.encode_png()
+}
+
+/// Encodes a whole render: sprite sheets as a single PNG with `padding`
+/// between their cells, animations in the given `format` and still images
+/// as a PNG.
+pub fn encode_render(
+    render: &Render,
+    format: Format,
+    padding: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    match (render.sheet, render.results.as_slice()) {
+        (Some(grid), results) => {
+            encode_sheet(results, &render.palette, render.frame, grid, padding)
+        }
+        (None, [result]) => encode(result, &render.palette, render.frame),
+        (None, results) => encode_animation(
+            results,
+            &render.delays,
+            &render.palette,
+            render.frame,
+            format,
+        ),
+    }
+}
+
+/// Encodes the indexed frames of an animation as a GIF or an APNG, upscaled
+/// back to the source size.
+pub fn encode_animation(
+    results: &[Indexed],
+    delays: &[Duration],
+    palette: &[Rgb],
+    frame: Frame,
+    format: Format,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let frames = results
+        .iter()
+        .zip(delays)
+        .map(|(result, &delay)| Ok((upscale(result, palette, frame)?.rgba()?, delay)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    match format {
+        Format::Gif => animation::encode_gif(frame.src_size, &frames, palette),
+        Format::Apng => animation::encode_apng(frame.src_size, &frames),
+        Format::Frames => Err("numbered frames are encoded one by one".into()),
+    }
+}
+
+/// Encodes the indexed cells of a sprite sheet as a single PNG, each cell
+/// being upscaled back to its source size and `padding` pixels apart.
+pub fn encode_sheet(
+    results: &[Indexed],
+    palette: &[Rgb],
+    frame: Frame,
+    grid: Grid,
+    padding: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    assemble(results, palette, frame, grid, padding)?.encode_png()
+}
+
+/// Encodes the unique tiles of a tilemap as a PNG, at the downscaled
+/// resolution the map is laid out in.
+pub fn encode_tileset(tilemap: &Tilemap, palette: &[Rgb]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (width, height) = tilemap.tileset_size();
+    Backend::from_rgba(width, height, &tilemap.tileset(palette))?.encode_png()
+}
+
+/// Encodes the indexed results as an SVG at the source size, the cells of a
+/// sprite sheet being laid out `padding` pixels apart and the first frame
+/// standing for an animation.
+pub fn encode_svg(
+    results: &[Indexed],
+    palette: &[Rgb],
+    frame: Frame,
+    sheet: Option<Grid>,
+    padding: usize,
+) -> String {
+    match sheet {
+        Some(grid) => {
+            let cells = results
+                .iter()
+                .enumerate()
+                .map(|(i, result)| (result, grid.offset(i, frame.src_size, padding)))
+                .collect::<Vec<_>>();
+            svg::encode(
+                grid.sheet_size(frame.src_size, padding),
+                &cells,
+                palette,
+                frame,
+            )
+        }
+        None => {
+            let cells = results
+                .first()
+                .map(|result| (result, (0, 0)))
+                .into_iter()
+                .collect::<Vec<_>>();
+            svg::encode(frame.src_size, &cells, palette, frame)
+        }
+    }
+}
+
+/// Same as `encode` with a checkerboard behind the transparent pixels, so
+/// that they stand out in the preview.
+pub fn preview(result: &Indexed, palette: &[Rgb], frame: Frame) -> Result<Vec<u8>, Box<dyn Error>> {
+    let image = upscale(result, palette, frame)?;
+    if result.opaque.is_none() {
+        return image.encode_png();
+    }
+    checkerboard(image)
+}
+
+/// Same as `encode_sheet` with a checkerboard behind the transparent pixels.
+pub fn preview_sheet(
+    results: &[Indexed],
+    palette: &[Rgb],
+    frame: Frame,
+    grid: Grid,
+    padding: usize,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    checkerboard(assemble(results, palette, frame, grid, padding)?)
+}
+
+fn checkerboard(image: Backend) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (width, height) = (image.width(), image.height());
+    let mut pixels = image.rgba()?;
+    for (p, px) in pixels.chunks_exact_mut(4).enumerate() {
+        let (x, y) = (p % width, p / width);
+        let checker = [204, 153][(x / CHECKER_SIZE + y / CHECKER_SIZE) % 2];
+        let alpha = px[3] as u32;
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * alpha + checker * (255 - alpha)) / 255) as u8;
+        }
+        px[3] = 255;
+    }
+
+    Backend::from_rgba(width, height, &pixels)?.encode_png()
+}
+
+fn assemble(
+    results: &[Indexed],
+    palette: &[Rgb],
+    frame: Frame,
+    grid: Grid,
+    padding: usize,
+) -> Result<Backend, Box<dyn Error>> {
+    let cells = results
+        .iter()
+        .map(|result| upscale(result, palette, frame)?.rgba())
+        .collect::<Result<Vec<_>, _>>()?;
+    let (width, height) = grid.sheet_size(frame.src_size, padding);
+    Backend::from_rgba(
+        width,
+        height,
+        &sheet::assemble(&cells, frame.src_size, grid, padding),
+    )
+}
+
+fn upscale(result: &Indexed, palette: &[Rgb], frame: Frame) -> Result<Backend, Box<dyn Error>> {
+    let mut image = match result.opaque {
+        Some(_) => Backend::from_rgba(result.width, result.height, &result.to_rgba(palette))?,
+        None => Backend::from_rgb(result.width, result.height, &result.to_rgb(palette))?,
+    };
+    image.resize(frame.size.0, frame.size.1, Filter::Point);
+    if frame.size != frame.src_size {
+        let (x, y) = frame.offset;
+        let (width, height) = frame.src_size;
+        image.crop(x, y, width, height)?;
+    }
+    Ok(image)
+}
+
+/// Places packed 8-bit RGBA pixels at `offset` within a `padded` image,
+/// repeating their edges all around.
+fn pad(
+    pixels: &[u8],
+    (width, height): (usize, usize),
+    (left, top): (usize, usize),
+    (padded_width, padded_height): (usize, usize),
+) -> Vec<u8> {
+    let mut padded = Vec::with_capacity(padded_width * padded_height * 4);
+    for y in 0..padded_height {
+        let src_y = y.saturating_sub(top).min(height - 1);
+        for x in 0..padded_width {
+            let src_x = x.saturating_sub(left).min(width - 1);
+            let p = (src_y * width + src_x) * 4;
+            padded.extend_from_slice(&pixels[p..p + 4]);
+        }
+    }
+    padded
+}
+
+/// Averages each `block` of packed 8-bit RGBA pixels of the given `width`
+/// into a single pixel, the image being a whole number of blocks.
+///
+/// Colors are weighted by their alpha so that transparent pixels do not
+/// bleed into the opaque ones.
+fn block_means(pixels: &[u8], width: usize, (block_x, block_y): (usize, usize)) -> Vec<u8> {
+    let height = pixels.len() / 4 / width.max(1);
+    let (columns, rows) = (width / block_x, height / block_y);
+    let area = (block_x * block_y) as u64;
+
+    let mut means = Vec::with_capacity(columns * rows * 4);
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut sums = [0u64; 4];
+            for y in row * block_y..(row + 1) * block_y {
+                for x in column * block_x..(column + 1) * block_x {
+                    let p = (y * width + x) * 4;
+                    let alpha = pixels[p + 3] as u64;
+                    for c in 0..3 {
+                        sums[c] += pixels[p + c] as u64 * alpha;
+                    }
+                    sums[3] += alpha;
+                }
+            }
+
+            for &sum in &sums[..3] {
+                means.push(match sums[3] {
+                    0 => 0,
+                    alpha => ((sum + alpha / 2) / alpha) as u8,
+                });
+            }
+            means.push(((sums[3] + area / 2) / area) as u8);
+        }
+    }
+    means
+}
+
+/// Keeps the `stride` sized chunks of `values` whose pixel is opaque, all of
+/// them without a mask.
+fn opaque_only<T: Copy>(values: &[T], stride: usize, opaque: Option<&[bool]>) -> Vec<T> {
+    match opaque {
+        Some(opaque) => values
+            .chunks_exact(stride)
+            .zip(opaque)
+            .filter(|(_, &opaque)| opaque)
+            .flat_map(|(chunk, _)| chunk.iter().copied())
+            .collect(),
+        None => values.to_vec(),
+    }
+}
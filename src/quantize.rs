@@ -0,0 +1,657 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::color::{self, ColorSpace, Rgb};
+use crate::palette;
+
+/// Algorithm picking the colors of the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantizer {
+    /// K-means of the image backend, seeded with k-means++.
+    ///
+    /// Only offered with the `magick` feature, where ImageMagick clusters the
+    /// image: the pure Rust backend runs the same k-means as
+    /// [`Quantizer::KmeansPp`].
+    Kmeans,
+    MedianCut,
+    Octree,
+    Wu,
+    /// Pure Rust k-means, seeded with k-means++.
+    KmeansPp,
+}
+
+impl Quantizer {
+    pub const ALL: [Quantizer; 5] = [
+        Quantizer::Kmeans,
+        Quantizer::MedianCut,
+        Quantizer::Octree,
+        Quantizer::Wu,
+        Quantizer::KmeansPp,
+    ];
+
+    /// The quantizers offered to pick from, which leave out the duplicate of
+    /// k-means++ without ImageMagick.
+    #[cfg(feature = "magick")]
+    pub const AVAILABLE: &[Quantizer] = &Self::ALL;
+    #[cfg(not(feature = "magick"))]
+    pub const AVAILABLE: &[Quantizer] = &[
+        Quantizer::MedianCut,
+        Quantizer::Octree,
+        Quantizer::Wu,
+        Quantizer::KmeansPp,
+    ];
+
+    /// Whether the palette is the outcome of a k-means clustering, which can
+    /// be refined around locked colors.
+    pub fn is_kmeans(self) -> bool {
+        matches!(self, Self::Kmeans | Self::KmeansPp)
+    }
+
+    /// Whether the colors are clustered in the chosen color space, the others
+    /// always clustering in RGB.
+    pub fn uses_space(self) -> bool {
+        self == Self::KmeansPp
+    }
+
+    /// Computes a palette of at most `k` colors from packed 8-bit RGB pixels.
+    ///
+    /// Returns `None` for [`Quantizer::Kmeans`] which runs on the image itself.
+    pub fn palette(
+        self,
+        pixels: &[u8],
+        k: usize,
+        space: ColorSpace,
+        rng: &mut Rng,
+    ) -> Option<Vec<Rgb>> {
+        match self {
+            Self::Kmeans => None,
+            Self::MedianCut => Some(median_cut(pixels, k)),
+            Self::Octree => Some(octree(pixels, k)),
+            Self::Wu => Some(wu(pixels, k)),
+            Self::KmeansPp => Some(kmeans_pp(pixels, k, space, rng)),
+        }
+    }
+}
+
+impl Default for Quantizer {
+    #[cfg(feature = "magick")]
+    fn default() -> Self {
+        Self::Kmeans
+    }
+
+    #[cfg(not(feature = "magick"))]
+    fn default() -> Self {
+        Self::KmeansPp
+    }
+}
+
+impl fmt::Display for Quantizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Kmeans => "k-means",
+                Self::MedianCut => "median cut",
+                Self::Octree => "octree",
+                Self::Wu => "Wu",
+                Self::KmeansPp => "k-means++",
+            }
+        )
+    }
+}
+
+impl FromStr for Quantizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kmeans" => Ok(Self::Kmeans),
+            "median-cut" => Ok(Self::MedianCut),
+            "octree" => Ok(Self::Octree),
+            "wu" => Ok(Self::Wu),
+            "kmeans++" => Ok(Self::KmeansPp),
+            _ => Err(format!("unknown quantizer: {}", s)),
+        }
+    }
+}
+
+/// SplitMix64 generator.
+///
+/// Kept in-house so that a given seed yields the same sequence whatever the
+/// dependency versions, which reproducible renders rely on.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Distinct colors of packed 8-bit RGB pixels with their counts, in a stable
+/// order.
+pub fn color_counts(pixels: &[u8]) -> BTreeMap<Rgb, u64> {
+    let mut counts = BTreeMap::new();
+    for px in pixels.chunks_exact(3) {
+        *counts.entry(Rgb::new(px[0], px[1], px[2])).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Picks up to `k` initial centroids among the pixel colors, following the
+/// k-means++ strategy in `space`.
+pub fn kmeans_pp_seeds(pixels: &[u8], k: usize, space: ColorSpace, rng: &mut Rng) -> Vec<Rgb> {
+    let colors = color_counts(pixels)
+        .into_iter()
+        .map(|(color, count)| (color, space.coords(color), count as f64))
+        .collect::<Vec<_>>();
+
+    let mut seeds = vec![];
+    if colors.is_empty() || k == 0 {
+        return seeds;
+    }
+
+    let pick = |weights: &[f64], rng: &mut Rng| {
+        let total = weights.iter().sum::<f64>();
+        let mut target = rng.next_f64() * total;
+        for (i, &weight) in weights.iter().enumerate() {
+            if target < weight {
+                return i;
+            }
+            target -= weight;
+        }
+        weights.len() - 1
+    };
+
+    let counts = colors
+        .iter()
+        .map(|&(_, _, count)| count)
+        .collect::<Vec<_>>();
+    let first = pick(&counts, rng);
+    seeds.push(colors[first].0);
+
+    let mut distances = colors
+        .iter()
+        .map(|(_, coords, _)| color::squared(*coords, colors[first].1))
+        .collect::<Vec<_>>();
+
+    while seeds.len() < k {
+        let weights = colors
+            .iter()
+            .zip(&distances)
+            .map(|(&(_, _, count), &distance)| count * distance)
+            .collect::<Vec<_>>();
+        if weights.iter().all(|&weight| weight == 0.0) {
+            break;
+        }
+
+        let next = pick(&weights, rng);
+        seeds.push(colors[next].0);
+        for (distance, (_, coords, _)) in distances.iter_mut().zip(&colors) {
+            *distance = distance.min(color::squared(*coords, colors[next].1));
+        }
+    }
+
+    seeds
+}
+
+/// Seeds with k-means++ then runs Lloyd iterations in `space`.
+pub fn kmeans_pp(pixels: &[u8], k: usize, space: ColorSpace, rng: &mut Rng) -> Vec<Rgb> {
+    let mut colors = kmeans_pp_seeds(pixels, k, space, rng);
+    let locked = vec![false; colors.len()];
+    palette::refine(pixels, &mut colors, &locked, space, 100);
+    colors
+}
+
+/// Heckbert's median cut: recursively splits the box with the widest channel
+/// range at its weighted median.
+pub fn median_cut(pixels: &[u8], k: usize) -> Vec<Rgb> {
+    let colors = color_counts(pixels).into_iter().collect::<Vec<_>>();
+    if colors.is_empty() || k == 0 {
+        return vec![];
+    }
+
+    let channel = |color: &Rgb, c: usize| [color.r, color.g, color.b][c];
+    let widest = |colors: &[(Rgb, u64)]| {
+        (0..3)
+            .map(|c| {
+                let min = colors.iter().map(|(color, _)| channel(color, c)).min();
+                let max = colors.iter().map(|(color, _)| channel(color, c)).max();
+                (c, max.unwrap_or(0) - min.unwrap_or(0))
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < k {
+        let splittable = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .max_by_key(|(_, colors)| widest(colors).1);
+        let i = match splittable {
+            Some((i, _)) => i,
+            None => break,
+        };
+
+        let mut lower = boxes.swap_remove(i);
+        let (c, _) = widest(&lower);
+        lower.sort_by_key(|(color, _)| (channel(color, c), *color));
+
+        let total = lower.iter().map(|(_, count)| count).sum::<u64>();
+        let mut acc = 0;
+        let mut split = lower.len() / 2;
+        for (j, (_, count)) in lower.iter().enumerate() {
+            acc += count;
+            if acc * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        let upper = lower.split_off(split.clamp(1, lower.len() - 1));
+
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|colors| mean(colors)).collect()
+}
+
+/// Octree quantization: colors are leaves of a depth 8 octree whose least
+/// populated nodes are folded, deepest first, until at most `k` remain.
+pub fn octree(pixels: &[u8], k: usize) -> Vec<Rgb> {
+    if k == 0 {
+        return vec![];
+    }
+
+    // Leaves keyed by depth and path, the path holding 3 bits per level
+    let mut leaves = BTreeMap::new();
+    for (color, count) in color_counts(pixels) {
+        let mut path = 0u32;
+        for bit in (0..8).rev() {
+            path = (path << 3)
+                | ((color.r as u32 >> bit) & 1) << 2
+                | ((color.g as u32 >> bit) & 1) << 1
+                | ((color.b as u32 >> bit) & 1);
+        }
+        leaves.insert((8u8, path), vec![(color, count)]);
+    }
+
+    'fold: for depth in (1..=8u8).rev() {
+        let mut parents = BTreeMap::new();
+        for &(leaf_depth, path) in leaves.keys() {
+            if leaf_depth == depth {
+                parents.entry(path >> 3).or_insert_with(Vec::new).push(path);
+            }
+        }
+
+        let mut parents = parents
+            .into_iter()
+            .map(|(parent, children)| {
+                let count = children
+                    .iter()
+                    .flat_map(|&child| &leaves[&(depth, child)])
+                    .map(|(_, count)| count)
+                    .sum::<u64>();
+                (count, parent, children)
+            })
+            .collect::<Vec<_>>();
+        parents.sort();
+
+        for (_, parent, children) in parents {
+            if leaves.len() <= k {
+                break 'fold;
+            }
+            let mut colors = vec![];
+            for child in children {
+                colors.extend(leaves.remove(&(depth, child)).unwrap_or_default());
+            }
+            leaves.insert((depth - 1, parent), colors);
+        }
+    }
+
+    leaves.values().map(|colors| mean(colors)).collect()
+}
+
+/// Xiaolin Wu's quantizer, greedily splitting boxes of a 32x32x32 histogram
+/// so as to minimize the variance of each box.
+pub fn wu(pixels: &[u8], k: usize) -> Vec<Rgb> {
+    let mut moments = WuMoments::new(pixels);
+    moments.accumulate();
+
+    let whole = WuBox {
+        r0: 0,
+        r1: 32,
+        g0: 0,
+        g1: 32,
+        b0: 0,
+        b1: 32,
+    };
+    let mut boxes = vec![whole; k.max(1)];
+    let mut variances = vec![0.0; k.max(1)];
+
+    let mut next = 0;
+    let mut i = 1;
+    while i < k {
+        if let Some(upper) = moments.cut(&mut boxes[next]) {
+            boxes[i] = upper;
+            variances[next] = moments.variance(&boxes[next]);
+            variances[i] = moments.variance(&boxes[i]);
+        } else {
+            variances[next] = 0.0;
+            i -= 1;
+        }
+
+        next = 0;
+        let mut max = variances[0];
+        for (j, &variance) in variances.iter().enumerate().take(i + 1).skip(1) {
+            if variance > max {
+                max = variance;
+                next = j;
+            }
+        }
+        if max <= 0.0 {
+            boxes.truncate(i + 1);
+            break;
+        }
+
+        i += 1;
+    }
+
+    boxes
+        .iter()
+        .filter_map(|cube| {
+            let weight = moments.volume(cube, &moments.wt);
+            if weight > 0.0 {
+                let c = |m: &[f64]| (moments.volume(cube, m) / weight).round() as u8;
+                Some(Rgb::new(c(&moments.mr), c(&moments.mg), c(&moments.mb)))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Bounds of a box of the Wu histogram, lower bounds being exclusive.
+#[derive(Debug, Clone, Copy)]
+struct WuBox {
+    r0: usize,
+    r1: usize,
+    g0: usize,
+    g1: usize,
+    b0: usize,
+    b1: usize,
+}
+
+impl WuBox {
+    fn volume(&self) -> usize {
+        (self.r1 - self.r0) * (self.g1 - self.g0) * (self.b1 - self.b0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Axis {
+    R,
+    G,
+    B,
+}
+
+/// Cumulative moments of the Wu histogram.
+struct WuMoments {
+    wt: Vec<f64>,
+    mr: Vec<f64>,
+    mg: Vec<f64>,
+    mb: Vec<f64>,
+    m2: Vec<f64>,
+}
+
+const WU_SIDE: usize = 33;
+
+fn wu_index(r: usize, g: usize, b: usize) -> usize {
+    r * WU_SIDE * WU_SIDE + g * WU_SIDE + b
+}
+
+impl WuMoments {
+    fn new(pixels: &[u8]) -> Self {
+        let size = WU_SIDE * WU_SIDE * WU_SIDE;
+        let mut moments = Self {
+            wt: vec![0.0; size],
+            mr: vec![0.0; size],
+            mg: vec![0.0; size],
+            mb: vec![0.0; size],
+            m2: vec![0.0; size],
+        };
+
+        for px in pixels.chunks_exact(3) {
+            let (r, g, b) = (px[0] as usize, px[1] as usize, px[2] as usize);
+            let i = wu_index((r >> 3) + 1, (g >> 3) + 1, (b >> 3) + 1);
+            moments.wt[i] += 1.0;
+            moments.mr[i] += r as f64;
+            moments.mg[i] += g as f64;
+            moments.mb[i] += b as f64;
+            moments.m2[i] += (r * r + g * g + b * b) as f64;
+        }
+
+        moments
+    }
+
+    /// Turns the histogram into cumulative moments, so that the moments of
+    /// any box are obtained by inclusion-exclusion of its corners.
+    fn accumulate(&mut self) {
+        for m in [
+            &mut self.wt,
+            &mut self.mr,
+            &mut self.mg,
+            &mut self.mb,
+            &mut self.m2,
+        ]
+        .iter_mut()
+        {
+            for r in 1..WU_SIDE {
+                let mut area = [0.0; WU_SIDE];
+                for g in 1..WU_SIDE {
+                    let mut line = 0.0;
+                    for b in 1..WU_SIDE {
+                        line += m[wu_index(r, g, b)];
+                        area[b] += line;
+                        m[wu_index(r, g, b)] = m[wu_index(r - 1, g, b)] + area[b];
+                    }
+                }
+            }
+        }
+    }
+
+    fn volume(&self, cube: &WuBox, m: &[f64]) -> f64 {
+        m[wu_index(cube.r1, cube.g1, cube.b1)]
+            - m[wu_index(cube.r1, cube.g1, cube.b0)]
+            - m[wu_index(cube.r1, cube.g0, cube.b1)]
+            + m[wu_index(cube.r1, cube.g0, cube.b0)]
+            - m[wu_index(cube.r0, cube.g1, cube.b1)]
+            + m[wu_index(cube.r0, cube.g1, cube.b0)]
+            + m[wu_index(cube.r0, cube.g0, cube.b1)]
+            - m[wu_index(cube.r0, cube.g0, cube.b0)]
+    }
+
+    /// Part of the volume that does not depend on the cut position.
+    fn bottom(&self, cube: &WuBox, axis: Axis, m: &[f64]) -> f64 {
+        match axis {
+            Axis::R => {
+                -m[wu_index(cube.r0, cube.g1, cube.b1)]
+                    + m[wu_index(cube.r0, cube.g1, cube.b0)]
+                    + m[wu_index(cube.r0, cube.g0, cube.b1)]
+                    - m[wu_index(cube.r0, cube.g0, cube.b0)]
+            }
+            Axis::G => {
+                -m[wu_index(cube.r1, cube.g0, cube.b1)]
+                    + m[wu_index(cube.r1, cube.g0, cube.b0)]
+                    + m[wu_index(cube.r0, cube.g0, cube.b1)]
+                    - m[wu_index(cube.r0, cube.g0, cube.b0)]
+            }
+            Axis::B => {
+                -m[wu_index(cube.r1, cube.g1, cube.b0)]
+                    + m[wu_index(cube.r1, cube.g0, cube.b0)]
+                    + m[wu_index(cube.r0, cube.g1, cube.b0)]
+                    - m[wu_index(cube.r0, cube.g0, cube.b0)]
+            }
+        }
+    }
+
+    /// Part of the volume that depends on the cut position `pos`.
+    fn top(&self, cube: &WuBox, axis: Axis, pos: usize, m: &[f64]) -> f64 {
+        match axis {
+            Axis::R => {
+                m[wu_index(pos, cube.g1, cube.b1)]
+                    - m[wu_index(pos, cube.g1, cube.b0)]
+                    - m[wu_index(pos, cube.g0, cube.b1)]
+                    + m[wu_index(pos, cube.g0, cube.b0)]
+            }
+            Axis::G => {
+                m[wu_index(cube.r1, pos, cube.b1)]
+                    - m[wu_index(cube.r1, pos, cube.b0)]
+                    - m[wu_index(cube.r0, pos, cube.b1)]
+                    + m[wu_index(cube.r0, pos, cube.b0)]
+            }
+            Axis::B => {
+                m[wu_index(cube.r1, cube.g1, pos)]
+                    - m[wu_index(cube.r1, cube.g0, pos)]
+                    - m[wu_index(cube.r0, cube.g1, pos)]
+                    + m[wu_index(cube.r0, cube.g0, pos)]
+            }
+        }
+    }
+
+    fn variance(&self, cube: &WuBox) -> f64 {
+        if cube.volume() <= 1 {
+            return 0.0;
+        }
+        let dr = self.volume(cube, &self.mr);
+        let dg = self.volume(cube, &self.mg);
+        let db = self.volume(cube, &self.mb);
+        let weight = self.volume(cube, &self.wt);
+        if weight == 0.0 {
+            return 0.0;
+        }
+        self.volume(cube, &self.m2) - (dr * dr + dg * dg + db * db) / weight
+    }
+
+    /// Best cut position along `axis` and the resulting variance reduction.
+    fn maximize(
+        &self,
+        cube: &WuBox,
+        axis: Axis,
+        first: usize,
+        last: usize,
+    ) -> (f64, Option<usize>) {
+        let whole_r = self.volume(cube, &self.mr);
+        let whole_g = self.volume(cube, &self.mg);
+        let whole_b = self.volume(cube, &self.mb);
+        let whole_w = self.volume(cube, &self.wt);
+
+        let base_r = self.bottom(cube, axis, &self.mr);
+        let base_g = self.bottom(cube, axis, &self.mg);
+        let base_b = self.bottom(cube, axis, &self.mb);
+        let base_w = self.bottom(cube, axis, &self.wt);
+
+        let mut max = 0.0;
+        let mut cut = None;
+        for pos in first..last {
+            let half_r = base_r + self.top(cube, axis, pos, &self.mr);
+            let half_g = base_g + self.top(cube, axis, pos, &self.mg);
+            let half_b = base_b + self.top(cube, axis, pos, &self.mb);
+            let half_w = base_w + self.top(cube, axis, pos, &self.wt);
+            if half_w == 0.0 || half_w == whole_w {
+                continue;
+            }
+
+            let lower = (half_r * half_r + half_g * half_g + half_b * half_b) / half_w;
+            let (upper_r, upper_g, upper_b) =
+                (whole_r - half_r, whole_g - half_g, whole_b - half_b);
+            let upper =
+                (upper_r * upper_r + upper_g * upper_g + upper_b * upper_b) / (whole_w - half_w);
+
+            if lower + upper > max {
+                max = lower + upper;
+                cut = Some(pos);
+            }
+        }
+
+        (max, cut)
+    }
+
+    /// Splits `cube` in two along its best axis, returning the upper part.
+    fn cut(&self, cube: &mut WuBox) -> Option<WuBox> {
+        let (max_r, cut_r) = self.maximize(cube, Axis::R, cube.r0 + 1, cube.r1);
+        let (max_g, cut_g) = self.maximize(cube, Axis::G, cube.g0 + 1, cube.g1);
+        let (max_b, cut_b) = self.maximize(cube, Axis::B, cube.b0 + 1, cube.b1);
+
+        let mut upper = *cube;
+        if max_r >= max_g && max_r >= max_b {
+            let cut = cut_r?;
+            cube.r1 = cut;
+            upper.r0 = cut;
+        } else if max_g >= max_r && max_g >= max_b {
+            let cut = cut_g?;
+            cube.g1 = cut;
+            upper.g0 = cut;
+        } else {
+            let cut = cut_b?;
+            cube.b1 = cut;
+            upper.b0 = cut;
+        }
+
+        Some(upper)
+    }
+}
+
+/// Weighted mean of colors.
+fn mean(colors: &[(Rgb, u64)]) -> Rgb {
+    let mut sums = [0u64; 4];
+    for (color, count) in colors {
+        sums[0] += color.r as u64 * count;
+        sums[1] += color.g as u64 * count;
+        sums[2] += color.b as u64 * count;
+        sums[3] += count;
+    }
+    let n = sums[3].max(1);
+    Rgb::new(
+        ((sums[0] + n / 2) / n) as u8,
+        ((sums[1] + n / 2) / n) as u8,
+        ((sums[2] + n / 2) / n) as u8,
+    )
+}
+
+/// Root mean square error, in RGB units, of an indexed image against the
+/// packed 8-bit RGB pixels it was made from.
+pub fn rmse(pixels: &[u8], indices: &[usize], palette: &[Rgb]) -> f64 {
+    if indices.is_empty() {
+        return 0.0;
+    }
+    let sum = pixels
+        .chunks_exact(3)
+        .zip(indices)
+        .map(|(px, &i)| {
+            let color = palette[i];
+            let dr = px[0] as f64 - color.r as f64;
+            let dg = px[1] as f64 - color.g as f64;
+            let db = px[2] as f64 - color.b as f64;
+            dr * dr + dg * dg + db * db
+        })
+        .sum::<f64>();
+    (sum / (indices.len() * 3) as f64).sqrt()
+}
@@ -0,0 +1,250 @@
+use std::fmt;
+use std::fmt::Write;
+
+/// How the source sheet is cut into cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slicing {
+    /// Cells of a given size, in source pixels.
+    Size,
+    /// A given number of columns and rows.
+    Count,
+}
+
+impl Slicing {
+    pub const ALL: [Slicing; 2] = [Slicing::Size, Slicing::Count];
+}
+
+impl Default for Slicing {
+    fn default() -> Self {
+        Self::Size
+    }
+}
+
+impl fmt::Display for Slicing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Size => "cell size",
+                Self::Count => "cell count",
+            }
+        )
+    }
+}
+
+/// Format of the atlas describing where the cells lie in the exported sheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Atlas {
+    /// A `frames` array, as TexturePacker's JSON array.
+    Json,
+    /// A `TextureAtlas` element, as Starling's XML.
+    Xml,
+}
+
+impl Atlas {
+    pub const ALL: [Atlas; 2] = [Atlas::Json, Atlas::Xml];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Xml => "xml",
+        }
+    }
+}
+
+impl Default for Atlas {
+    fn default() -> Self {
+        Self::Json
+    }
+}
+
+impl fmt::Display for Atlas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Json => "JSON",
+                Self::Xml => "XML",
+            }
+        )
+    }
+}
+
+/// Layout of the cells of a sheet, read row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+    /// Width and height of a cell, in source pixels.
+    pub cell: (usize, usize),
+}
+
+impl Grid {
+    /// Cuts a `width` by `height` sheet, leftover pixels past the last whole
+    /// cells being dropped.
+    pub fn new(
+        slicing: Slicing,
+        cell_size: (u16, u16),
+        cell_count: (u8, u8),
+        (width, height): (usize, usize),
+    ) -> Self {
+        let (cell_width, cell_height) = match slicing {
+            Slicing::Size => (
+                (cell_size.0 as usize).clamp(1, width.max(1)),
+                (cell_size.1 as usize).clamp(1, height.max(1)),
+            ),
+            Slicing::Count => (
+                (width / cell_count.0.max(1) as usize).max(1),
+                (height / cell_count.1.max(1) as usize).max(1),
+            ),
+        };
+
+        Self {
+            columns: (width / cell_width).max(1),
+            rows: (height / cell_height).max(1),
+            cell: (cell_width, cell_height),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Source rectangle of each cell, as `[x, y, width, height]`.
+    pub fn cells(&self) -> impl Iterator<Item = [usize; 4]> + '_ {
+        (0..self.len()).map(move |i| {
+            let (column, row) = (i % self.columns, i / self.columns);
+            [
+                column * self.cell.0,
+                row * self.cell.1,
+                self.cell.0,
+                self.cell.1,
+            ]
+        })
+    }
+
+    /// Where cell `i` lies in the sheet reassembled from `cell` sized cells,
+    /// `padding` pixels apart.
+    pub fn offset(&self, i: usize, cell: (usize, usize), padding: usize) -> (usize, usize) {
+        (
+            (i % self.columns) * (cell.0 + padding),
+            (i / self.columns) * (cell.1 + padding),
+        )
+    }
+
+    /// Size of the sheet reassembled from `cell` sized cells, `padding`
+    /// pixels apart.
+    pub fn sheet_size(&self, cell: (usize, usize), padding: usize) -> (usize, usize) {
+        (
+            self.columns * cell.0 + (self.columns - 1) * padding,
+            self.rows * cell.1 + (self.rows - 1) * padding,
+        )
+    }
+}
+
+/// Copies the `[x, y, width, height]` rectangle out of packed 8-bit RGBA
+/// pixels of the given `width`.
+pub fn cut(pixels: &[u8], width: usize, [x, y, w, h]: [usize; 4]) -> Vec<u8> {
+    let mut cell = Vec::with_capacity(w * h * 4);
+    for row in y..y + h {
+        let start = (row * width + x) * 4;
+        cell.extend_from_slice(&pixels[start..start + w * 4]);
+    }
+    cell
+}
+
+/// Lays out RGBA cells of size `cell` along `grid`, `padding` transparent
+/// pixels apart.
+pub fn assemble(cells: &[Vec<u8>], cell: (usize, usize), grid: Grid, padding: usize) -> Vec<u8> {
+    let (width, height) = grid.sheet_size(cell, padding);
+    let mut sheet = vec![0; width * height * 4];
+    for (i, pixels) in cells.iter().enumerate() {
+        let (x, y) = grid.offset(i, cell, padding);
+        for row in 0..cell.1 {
+            let start = ((y + row) * width + x) * 4;
+            sheet[start..start + cell.0 * 4]
+                .copy_from_slice(&pixels[row * cell.0 * 4..(row + 1) * cell.0 * 4]);
+        }
+    }
+    sheet
+}
+
+/// Describes the cells of the sheet saved as `image`, named after it and
+/// numbered row by row.
+pub fn atlas(
+    format: Atlas,
+    image: &str,
+    grid: Grid,
+    cell: (usize, usize),
+    padding: usize,
+) -> String {
+    let name = image.rsplitn(2, '.').last().unwrap_or(image);
+    let (width, height) = grid.sheet_size(cell, padding);
+    let frames = (0..grid.len()).map(|i| {
+        let (x, y) = grid.offset(i, cell, padding);
+        (format!("{}_{:03}", name, i), x, y)
+    });
+
+    // Writing to a `String` cannot fail
+    let mut atlas = String::new();
+    match format {
+        Atlas::Json => {
+            atlas.push_str("{\n  \"frames\": [\n");
+            for (i, (name, x, y)) in frames.enumerate() {
+                let _ = write!(
+                    atlas,
+                    "    {{ \"filename\": \"{}\", \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }} }}",
+                    escape_json(&name),
+                    x,
+                    y,
+                    cell.0,
+                    cell.1
+                );
+                atlas.push_str(if i + 1 < grid.len() { ",\n" } else { "\n" });
+            }
+            let _ = write!(
+                atlas,
+                "  ],\n  \"meta\": {{ \"image\": \"{}\", \"size\": {{ \"w\": {}, \"h\": {} }} }}\n}}\n",
+                escape_json(image),
+                width,
+                height
+            );
+        }
+        Atlas::Xml => {
+            atlas.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            let _ = writeln!(
+                atlas,
+                "<TextureAtlas imagePath=\"{}\" width=\"{}\" height=\"{}\">",
+                escape_xml(image),
+                width,
+                height
+            );
+            for (name, x, y) in frames {
+                let _ = writeln!(
+                    atlas,
+                    "    <SubTexture name=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                    escape_xml(&name),
+                    x,
+                    y,
+                    cell.0,
+                    cell.1
+                );
+            }
+            atlas.push_str("</TextureAtlas>\n");
+        }
+    }
+    atlas
+}
+
+pub fn escape_json(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::backend::ImageBackend;
+use crate::params::{Channel, Levels};
+
+/// An entry of the adjustment stack, run in order by the pipeline.
+///
+/// The `Downscale` and `Quantize` markers always appear once, adjustments
+/// before `Downscale` running at the source size and the ones after
+/// `Quantize` recoloring the palette, which only pointwise ones can.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub enabled: bool,
+    pub adjustment: Adjustment,
+}
+
+impl Stage {
+    pub fn new(adjustment: Adjustment) -> Self {
+        Self {
+            enabled: true,
+            adjustment,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Adjustment {
+    Downscale,
+    Quantize,
+    Levels {
+        per_channel: bool,
+        levels: Levels,
+        channels: [Levels; 3],
+    },
+    /// In percent, 100 leaving the image untouched.
+    Modulate {
+        brightness: u8,
+        saturation: u8,
+        hue: u8,
+    },
+    Contrast {
+        sigmoidal: bool,
+        /// In percent, 100 leaving the image untouched.
+        amount: u8,
+        /// Center of the sigmoidal contrast, in percent.
+        midpoint: u8,
+    },
+    Unsharp {
+        /// Radius of the blur, in tenths of a pixel.
+        sigma: u8,
+        amount: u8,
+        threshold: u8,
+    },
+    /// Mostly useful before the downscale, to reduce noise.
+    Blur {
+        /// In tenths of a pixel.
+        sigma: u8,
+    },
+    Posterize {
+        levels: u8,
+    },
+}
+
+impl Adjustment {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Downscale => Kind::Downscale,
+            Self::Quantize => Kind::Quantize,
+            Self::Levels { .. } => Kind::Levels,
+            Self::Modulate { .. } => Kind::Modulate,
+            Self::Contrast { .. } => Kind::Contrast,
+            Self::Unsharp { .. } => Kind::Unsharp,
+            Self::Blur { .. } => Kind::Blur,
+            Self::Posterize { .. } => Kind::Posterize,
+        }
+    }
+
+    /// Whether the adjustment only depends on the color of each pixel, which
+    /// is required to recolor a palette.
+    pub fn is_pointwise(&self) -> bool {
+        !matches!(self, Self::Unsharp { .. } | Self::Blur { .. })
+    }
+
+    pub fn apply(&self, image: &mut impl ImageBackend) -> Result<(), Box<dyn Error>> {
+        match *self {
+            Self::Downscale | Self::Quantize => Ok(()),
+            Self::Levels {
+                per_channel,
+                levels,
+                channels,
+            } => {
+                if per_channel {
+                    for channel in Channel::ALL.iter().copied() {
+                        image.level(Some(channel), channels[channel.index()])?;
+                    }
+                    Ok(())
+                } else {
+                    image.level(None, levels)
+                }
+            }
+            Self::Modulate {
+                brightness,
+                saturation,
+                hue,
+            } => image.modulate(brightness, saturation, hue),
+            Self::Contrast {
+                sigmoidal,
+                amount,
+                midpoint,
+            } => {
+                if sigmoidal {
+                    // Strengths past 10 barely differ from a threshold
+                    let strength = (amount as f64 - 100.0) / 10.0;
+                    image.sigmoidal_contrast(
+                        strength > 0.0,
+                        strength.abs(),
+                        midpoint as f64 / 100.0,
+                    )
+                } else {
+                    image.contrast(amount as f64 - 100.0)
+                }
+            }
+            Self::Unsharp {
+                sigma,
+                amount,
+                threshold,
+            } => image.unsharp(
+                sigma as f64 / 10.0,
+                amount as f64 / 100.0,
+                threshold as f64 / 100.0,
+            ),
+            Self::Blur { sigma } => image.blur(sigma as f64 / 10.0),
+            Self::Posterize { levels } => image.posterize(levels),
+        }
+    }
+}
+
+/// The adjustments that can be added to the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Downscale,
+    Quantize,
+    Levels,
+    Modulate,
+    Contrast,
+    Unsharp,
+    Blur,
+    Posterize,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 6] = [
+        Kind::Levels,
+        Kind::Modulate,
+        Kind::Contrast,
+        Kind::Unsharp,
+        Kind::Blur,
+        Kind::Posterize,
+    ];
+
+    pub fn is_marker(self) -> bool {
+        matches!(self, Self::Downscale | Self::Quantize)
+    }
+
+    pub fn adjustment(self) -> Adjustment {
+        match self {
+            Self::Downscale => Adjustment::Downscale,
+            Self::Quantize => Adjustment::Quantize,
+            Self::Levels => Adjustment::Levels {
+                per_channel: false,
+                levels: Levels::default(),
+                channels: [Levels::default(); 3],
+            },
+            Self::Modulate => Adjustment::Modulate {
+                brightness: 100,
+                saturation: 100,
+                hue: 100,
+            },
+            Self::Contrast => Adjustment::Contrast {
+                sigmoidal: false,
+                amount: 100,
+                midpoint: 50,
+            },
+            Self::Unsharp => Adjustment::Unsharp {
+                sigma: 10,
+                amount: 100,
+                threshold: 5,
+            },
+            Self::Blur => Adjustment::Blur { sigma: 10 },
+            Self::Posterize => Adjustment::Posterize { levels: 8 },
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Downscale => "Downscale",
+                Self::Quantize => "Quantize",
+                Self::Levels => "Levels",
+                Self::Modulate => "Modulate",
+                Self::Contrast => "Contrast",
+                Self::Unsharp => "Unsharp",
+                Self::Blur => "Blur",
+                Self::Posterize => "Posterize",
+            }
+        )
+    }
+}
+
+/// The stack the editor starts with, leveling the downscaled image.
+pub fn initial() -> Vec<Stage> {
+    vec![
+        Stage::new(Adjustment::Downscale),
+        Stage::new(Adjustment::Levels {
+            per_channel: false,
+            levels: Levels {
+                black: 10,
+                white: 80,
+                gamma: 100,
+            },
+            channels: [Levels::default(); 3],
+        }),
+        Stage::new(Adjustment::Quantize),
+    ]
+}
+
+/// Adds an adjustment of `kind`, blurs going before the downscale to reduce
+/// noise and everything else before the quantization.
+pub fn insert(stack: &mut Vec<Stage>, kind: Kind) {
+    let marker = if kind == Kind::Blur {
+        Kind::Downscale
+    } else {
+        Kind::Quantize
+    };
+    let at = position(stack, marker).unwrap_or(stack.len());
+    stack.insert(at, Stage::new(kind.adjustment()));
+}
+
+/// Swaps stage `i` with the next one, unless that would put the quantization
+/// before the downscale, or after it an adjustment that cannot recolor the
+/// palette.
+pub fn move_down(stack: &mut [Stage], i: usize) -> bool {
+    if i + 1 >= stack.len()
+        || (stack[i + 1].adjustment.kind() == Kind::Quantize
+            && (stack[i].adjustment.kind() == Kind::Downscale
+                || !stack[i].adjustment.is_pointwise()))
+    {
+        return false;
+    }
+    stack.swap(i, i + 1);
+    true
+}
+
+pub fn position(stack: &[Stage], kind: Kind) -> Option<usize> {
+    stack
+        .iter()
+        .position(|stage| stage.adjustment.kind() == kind)
+}
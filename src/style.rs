@@ -1,5 +1,6 @@
 use iced::{
     button, checkbox, container, pick_list, progress_bar, radio, scrollable, slider, text_input,
+    Background, Color,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -119,6 +120,56 @@ impl From<Theme> for Box<dyn checkbox::StyleSheet> {
     }
 }
 
+/// Plain colored box used to display palette colors.
+pub struct Swatch(pub Color);
+
+impl container::StyleSheet for Swatch {
+    fn style(&self) -> container::Style {
+        container::Style {
+            background: Some(Background::Color(self.0)),
+            border_width: 1.0,
+            border_color: Color::from_rgba(0.5, 0.5, 0.5, 0.5),
+            ..container::Style::default()
+        }
+    }
+}
+
+/// Clickable palette color, outlined when selected.
+pub struct SwatchButton {
+    pub color: Color,
+    pub selected: bool,
+}
+
+impl button::StyleSheet for SwatchButton {
+    fn active(&self) -> button::Style {
+        let luma = 0.299 * self.color.r + 0.587 * self.color.g + 0.114 * self.color.b;
+        button::Style {
+            background: Some(Background::Color(self.color)),
+            border_radius: 2.0,
+            border_width: if self.selected { 3.0 } else { 1.0 },
+            border_color: if self.selected {
+                Color::from_rgb(0.9, 0.6, 0.1)
+            } else {
+                Color::from_rgba(0.5, 0.5, 0.5, 0.5)
+            },
+            text_color: if luma > 0.5 {
+                Color::BLACK
+            } else {
+                Color::WHITE
+            },
+            ..button::Style::default()
+        }
+    }
+
+    fn hovered(&self) -> button::Style {
+        let active = self.active();
+        button::Style {
+            border_width: active.border_width.max(2.0),
+            ..active
+        }
+    }
+}
+
 mod light {
     use iced::{button, pick_list, Background, Color, Vector};
 
@@ -0,0 +1,106 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::color::Rgb;
+use crate::palette::Indexed;
+use crate::pipeline::Frame;
+
+/// Encodes indexed results as an SVG of `size` pixels, each placed at its
+/// offset and upscaled as described by `frame`.
+///
+/// Every row of a result is emitted as runs of same colored pixels, one
+/// `<rect>` per run, in the result's own pixel units so that the file stays
+/// resolution independent. The palette colors are CSS classes, transparent
+/// pixels are left out.
+pub fn encode(
+    (width, height): (usize, usize),
+    cells: &[(&Indexed, (usize, usize))],
+    palette: &[Rgb],
+    frame: Frame,
+) -> String {
+    // Writing to a `String` cannot fail
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\">",
+        width, height, width, height
+    );
+
+    svg.push_str("<style>\n");
+    for (i, color) in palette.iter().enumerate() {
+        let _ = writeln!(svg, ".c{}{{fill:{}}}", i, color);
+    }
+    svg.push_str("</style>\n");
+
+    for &(result, (x, y)) in cells {
+        if result.width == 0 || result.height == 0 {
+            continue;
+        }
+
+        // The result spans `frame.size`, cropped to the source at
+        // `frame.offset`
+        let scale_x = frame.size.0 as f64 / result.width as f64;
+        let scale_y = frame.size.1 as f64 / result.height as f64;
+        let _ = writeln!(
+            svg,
+            "<svg x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\" preserveAspectRatio=\"none\">",
+            x,
+            y,
+            frame.src_size.0,
+            frame.src_size.1,
+            frame.offset.0 as f64 / scale_x,
+            frame.offset.1 as f64 / scale_y,
+            frame.src_size.0 as f64 / scale_x,
+            frame.src_size.1 as f64 / scale_y,
+        );
+
+        for (row, run_x, run_width, index) in runs(result) {
+            let _ = writeln!(
+                svg,
+                "<rect class=\"c{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"1\"/>",
+                index, run_x, row, run_width
+            );
+        }
+
+        svg.push_str("</svg>\n");
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Whether `path` names an SVG file, which exports are then written as.
+pub fn has_extension(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => ext.to_string_lossy().eq_ignore_ascii_case("svg"),
+        None => false,
+    }
+}
+
+/// Runs of opaque pixels sharing a color, as their row, first column, length
+/// and palette index.
+fn runs(result: &Indexed) -> Vec<(usize, usize, usize, usize)> {
+    let mut runs = vec![];
+    for y in 0..result.height {
+        let mut x = 0;
+        while x < result.width {
+            let p = y * result.width + x;
+            if !result.is_opaque(p) {
+                x += 1;
+                continue;
+            }
+
+            let index = result.indices[p];
+            let mut end = x + 1;
+            while end < result.width
+                && result.is_opaque(p + end - x)
+                && result.indices[p + end - x] == index
+            {
+                end += 1;
+            }
+            runs.push((y, x, end - x, index));
+            x = end;
+        }
+    }
+    runs
+}
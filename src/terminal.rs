@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::color::Rgb;
+use crate::palette::Indexed;
+
+/// From the lightest to the darkest, as printed on paper.
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+/// Channel values of the 6x6x6 color cube of 256-color terminals.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How a result is drawn as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Half blocks in 24-bit colors, two pixels per character.
+    Truecolor,
+    /// Half blocks in the 256 colors of xterm.
+    Ansi256,
+    /// Characters of a brightness ramp, without colors.
+    Ascii,
+}
+
+impl Style {
+    /// The colored styles, which ANSI exports can be written in.
+    pub const ANSI: [Style; 2] = [Style::Truecolor, Style::Ansi256];
+
+    /// The style exports to `path` are written in, if it names a text file:
+    /// plain `.txt` or ANSI `.ans` in the `ansi` style.
+    pub fn from_extension(path: &Path, ansi: Style) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        match ext.as_str() {
+            "txt" => Some(Self::Ascii),
+            "ans" => Some(ansi),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::Truecolor
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Truecolor => "truecolor",
+                Self::Ansi256 => "256 colors",
+                Self::Ascii => "ASCII",
+            }
+        )
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truecolor" => Ok(Self::Truecolor),
+            "256" => Ok(Self::Ansi256),
+            "ascii" => Ok(Self::Ascii),
+            _ => Err(format!("unknown text style: {}", s)),
+        }
+    }
+}
+
+/// Draws the downscaled `result` as text, one line per row of characters.
+///
+/// Colored styles stack two pixels in each character with half blocks, the
+/// ASCII one doubles each pixel so that it stays about square.
+pub fn encode(result: &Indexed, palette: &[Rgb], style: Style) -> String {
+    let pixel = |x: usize, y: usize| {
+        let p = y * result.width + x;
+        if y < result.height && result.is_opaque(p) {
+            Some(palette[result.indices[p]])
+        } else {
+            None
+        }
+    };
+
+    // Writing to a `String` cannot fail
+    let mut text = String::new();
+    match style {
+        Style::Ascii => {
+            for y in 0..result.height {
+                for x in 0..result.width {
+                    let c = match pixel(x, y) {
+                        Some(color) => {
+                            let luma = 0.299 * color.r as f64
+                                + 0.587 * color.g as f64
+                                + 0.114 * color.b as f64;
+                            let level = ((255.0 - luma) / 256.0 * RAMP.len() as f64) as usize;
+                            RAMP[level.min(RAMP.len() - 1)] as char
+                        }
+                        None => ' ',
+                    };
+                    text.push(c);
+                    text.push(c);
+                }
+                text.push('\n');
+            }
+        }
+        Style::Truecolor | Style::Ansi256 => {
+            let sgr = |layer: u8, color: Rgb| match style {
+                Style::Truecolor => {
+                    format!("\x1b[{}8;2;{};{};{}m", layer, color.r, color.g, color.b)
+                }
+                _ => format!("\x1b[{}8;5;{}m", layer, xterm256(color)),
+            };
+
+            for y in (0..result.height).step_by(2) {
+                for x in 0..result.width {
+                    let _ = match (pixel(x, y), pixel(x, y + 1)) {
+                        (Some(top), Some(bottom)) => {
+                            write!(text, "{}{}\u{2580}", sgr(3, top), sgr(4, bottom))
+                        }
+                        (Some(top), None) => write!(text, "\x1b[49m{}\u{2580}", sgr(3, top)),
+                        (None, Some(bottom)) => {
+                            write!(text, "\x1b[49m{}\u{2584}", sgr(3, bottom))
+                        }
+                        (None, None) => write!(text, "\x1b[0m "),
+                    };
+                }
+                text.push_str("\x1b[0m\n");
+            }
+        }
+    }
+    text
+}
+
+/// Index of the color of the xterm 256-color palette closest to `color`,
+/// among its color cube and its grays.
+fn xterm256(color: Rgb) -> u8 {
+    let nearest_level = |c: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (
+        nearest_level(color.r),
+        nearest_level(color.g),
+        nearest_level(color.b),
+    );
+    let cube = Rgb::new(CUBE[r], CUBE[g], CUBE[b]);
+
+    // Grays go from 8 to 238 by steps of 10
+    let mean = (color.r as u32 + color.g as u32 + color.b as u32) / 3;
+    let gray_level = ((mean.max(8) - 8 + 5) / 10).min(23) as u8;
+    let gray_value = 8 + 10 * gray_level;
+    let gray = Rgb::new(gray_value, gray_value, gray_value);
+
+    let distance = |other: Rgb| {
+        let dr = color.r as i32 - other.r as i32;
+        let dg = color.g as i32 - other.g as i32;
+        let db = color.b as i32 - other.b as i32;
+        dr * dr + dg * dg + db * db
+    };
+    if distance(gray) < distance(cube) {
+        232 + gray_level
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
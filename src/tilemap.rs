@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::color::Rgb;
+use crate::palette::Indexed;
+use crate::sheet::{escape_json, escape_xml};
+
+/// Most tiles on a row of the tileset image.
+const TILESET_COLUMNS: usize = 16;
+
+/// Flags Tiled sets on the global tile IDs of flipped tiles.
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+
+/// Format of the map written along with the tileset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Tiled's XML format.
+    Tmx,
+    /// Tiled's JSON format.
+    Tmj,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Tmx, Format::Tmj];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Tmx => "tmx",
+            Self::Tmj => "tmj",
+        }
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Tmx
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Tmx => "TMX",
+                Self::Tmj => "TMJ",
+            }
+        )
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tmx" => Ok(Self::Tmx),
+            "tmj" => Ok(Self::Tmj),
+            _ => Err(format!("unknown tile map format: {}", s)),
+        }
+    }
+}
+
+/// Palette indices of the pixels of a tile row by row, `None` where
+/// transparent.
+type Tile = Vec<Option<usize>>;
+
+/// A result cut into square tiles, identical tiles being stored once.
+#[derive(Debug, Clone)]
+pub struct Tilemap {
+    /// Side of the tiles, in result pixels.
+    pub size: usize,
+    pub columns: usize,
+    pub rows: usize,
+    pub tiles: Vec<Tile>,
+    /// Tiled global ID of each cell of the map row by row, along with its
+    /// flip flags, 0 for fully transparent cells.
+    pub cells: Vec<u32>,
+}
+
+impl Tilemap {
+    /// Cuts `result` into `size` sided tiles, the ones past its edges being
+    /// padded with transparent pixels.
+    ///
+    /// With `flips`, a tile that is a mirror of a known one is stored as
+    /// that tile flipped.
+    pub fn new(result: &Indexed, size: usize, flips: bool) -> Self {
+        let size = size.max(1);
+        let columns = (0..result.width).step_by(size).len();
+        let rows = (0..result.height).step_by(size).len();
+
+        let mut tiles = vec![];
+        let mut known: HashMap<Tile, u32> = HashMap::new();
+        let mut cells = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let tile = cut(result, size, column * size, row * size);
+                if tile.iter().all(Option::is_none) {
+                    cells.push(0);
+                    continue;
+                }
+
+                if let Some(&gid) = known.get(&tile) {
+                    cells.push(gid);
+                    continue;
+                }
+
+                // Tiled global IDs start at 1
+                let gid = tiles.len() as u32 + 1;
+                if flips {
+                    let horizontal = flip(&tile, size, true, false);
+                    let vertical = flip(&tile, size, false, true);
+                    let both = flip(&tile, size, true, true);
+                    known
+                        .entry(horizontal)
+                        .or_insert(gid | FLIPPED_HORIZONTALLY);
+                    known.entry(vertical).or_insert(gid | FLIPPED_VERTICALLY);
+                    known
+                        .entry(both)
+                        .or_insert(gid | FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY);
+                }
+                known.insert(tile.clone(), gid);
+                tiles.push(tile);
+                cells.push(gid);
+            }
+        }
+
+        Self {
+            size,
+            columns,
+            rows,
+            tiles,
+            cells,
+        }
+    }
+
+    /// Width and height of the tileset image, in pixels.
+    pub fn tileset_size(&self) -> (usize, usize) {
+        let columns = self.tileset_columns();
+        let rows = self.tiles.chunks(columns).len();
+        (columns * self.size, rows.max(1) * self.size)
+    }
+
+    /// The unique tiles laid out row by row, as packed 8-bit RGBA pixels.
+    pub fn tileset(&self, palette: &[Rgb]) -> Vec<u8> {
+        let (width, height) = self.tileset_size();
+        let columns = self.tileset_columns();
+        let mut pixels = vec![0; width * height * 4];
+        for (t, tile) in self.tiles.iter().enumerate() {
+            let (x0, y0) = ((t % columns) * self.size, (t / columns) * self.size);
+            for (p, &index) in tile.iter().enumerate() {
+                if let Some(i) = index {
+                    let (x, y) = (x0 + p % self.size, y0 + p / self.size);
+                    let color = palette[i];
+                    let at = (y * width + x) * 4;
+                    pixels[at..at + 4].copy_from_slice(&[color.r, color.g, color.b, 255]);
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Describes the map for Tiled, its tileset being the image at
+    /// `tileset`.
+    pub fn map(&self, format: Format, tileset: &str) -> String {
+        let name = tileset.rsplitn(2, '.').last().unwrap_or(tileset);
+        let (image_width, image_height) = self.tileset_size();
+        let columns = self.tileset_columns();
+
+        // Writing to a `String` cannot fail
+        let mut map = String::new();
+        match format {
+            Format::Tmx => {
+                map.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+                let _ = writeln!(
+                    map,
+                    "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\" nextlayerid=\"2\" nextobjectid=\"1\">",
+                    self.columns, self.rows, self.size, self.size
+                );
+                let _ = writeln!(
+                    map,
+                    " <tileset firstgid=\"1\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" tilecount=\"{}\" columns=\"{}\">",
+                    escape_xml(name),
+                    self.size,
+                    self.size,
+                    self.tiles.len(),
+                    columns
+                );
+                let _ = writeln!(
+                    map,
+                    "  <image source=\"{}\" width=\"{}\" height=\"{}\"/>",
+                    escape_xml(tileset),
+                    image_width,
+                    image_height
+                );
+                map.push_str(" </tileset>\n");
+                let _ = writeln!(
+                    map,
+                    " <layer id=\"1\" name=\"Tiles\" width=\"{}\" height=\"{}\">",
+                    self.columns, self.rows
+                );
+                map.push_str("  <data encoding=\"csv\">\n");
+                let rows = self
+                    .cells
+                    .chunks(self.columns)
+                    .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+                    .collect::<Vec<_>>();
+                map.push_str(&rows.join(",\n"));
+                map.push_str("\n</data>\n </layer>\n</map>\n");
+            }
+            Format::Tmj => {
+                let data = self
+                    .cells
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let _ = write!(
+                    map,
+                    "{{\n  \"type\": \"map\",\n  \"version\": \"1.10\",\n  \"orientation\": \"orthogonal\",\n  \"renderorder\": \"right-down\",\n  \"width\": {},\n  \"height\": {},\n  \"tilewidth\": {},\n  \"tileheight\": {},\n  \"infinite\": false,\n  \"nextlayerid\": 2,\n  \"nextobjectid\": 1,\n",
+                    self.columns, self.rows, self.size, self.size
+                );
+                let _ = writeln!(
+                    map,
+                    "  \"tilesets\": [{{ \"firstgid\": 1, \"name\": \"{}\", \"image\": \"{}\", \"imagewidth\": {}, \"imageheight\": {}, \"tilewidth\": {}, \"tileheight\": {}, \"tilecount\": {}, \"columns\": {}, \"margin\": 0, \"spacing\": 0 }}],",
+                    escape_json(name),
+                    escape_json(tileset),
+                    image_width,
+                    image_height,
+                    self.size,
+                    self.size,
+                    self.tiles.len(),
+                    columns
+                );
+                let _ = write!(
+                    map,
+                    "  \"layers\": [{{ \"type\": \"tilelayer\", \"id\": 1, \"name\": \"Tiles\", \"x\": 0, \"y\": 0, \"width\": {}, \"height\": {}, \"opacity\": 1, \"visible\": true, \"data\": [{}] }}]\n}}\n",
+                    self.columns, self.rows, data
+                );
+            }
+        }
+        map
+    }
+
+    fn tileset_columns(&self) -> usize {
+        self.tiles.len().clamp(1, TILESET_COLUMNS)
+    }
+}
+
+/// Paths of the tileset image and of the map written along with an export
+/// to `path`.
+pub fn paths(path: &Path, format: Format) -> (PathBuf, PathBuf) {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    (
+        path.with_file_name(format!("{}_tileset.png", stem)),
+        path.with_file_name(format!("{}.{}", stem, format.extension())),
+    )
+}
+
+/// The `size` sided tile of `result` at `(x, y)`.
+fn cut(result: &Indexed, size: usize, x: usize, y: usize) -> Tile {
+    let mut tile = Vec::with_capacity(size * size);
+    for tile_y in y..y + size {
+        for tile_x in x..x + size {
+            let p = tile_y * result.width + tile_x;
+            tile.push(
+                if tile_x < result.width && tile_y < result.height && result.is_opaque(p) {
+                    Some(result.indices[p])
+                } else {
+                    None
+                },
+            );
+        }
+    }
+    tile
+}
+
+fn flip(tile: &[Option<usize>], size: usize, horizontal: bool, vertical: bool) -> Tile {
+    let mut flipped = Vec::with_capacity(tile.len());
+    for y in 0..size {
+        for x in 0..size {
+            let src_x = if horizontal { size - 1 - x } else { x };
+            let src_y = if vertical { size - 1 - y } else { y };
+            flipped.push(tile[src_y * size + src_x]);
+        }
+    }
+    flipped
+}
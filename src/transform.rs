@@ -0,0 +1,146 @@
+use std::fmt;
+
+/// Clockwise rotation of the source, in quarter turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::None,
+        Rotation::Quarter,
+        Rotation::Half,
+        Rotation::ThreeQuarters,
+    ];
+
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Quarter => 1,
+            Self::Half => 2,
+            Self::ThreeQuarters => 3,
+        }
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}°", self.quarter_turns() as u16 * 90)
+    }
+}
+
+/// Width to height ratio the crop rectangle is held to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aspect {
+    Free,
+    Square,
+    FourThree,
+    SixteenNine,
+}
+
+impl Aspect {
+    pub const ALL: [Aspect; 4] = [
+        Aspect::Free,
+        Aspect::Square,
+        Aspect::FourThree,
+        Aspect::SixteenNine,
+    ];
+
+    pub fn ratio(self) -> Option<f64> {
+        match self {
+            Self::Free => None,
+            Self::Square => Some(1.0),
+            Self::FourThree => Some(4.0 / 3.0),
+            Self::SixteenNine => Some(16.0 / 9.0),
+        }
+    }
+}
+
+impl Default for Aspect {
+    fn default() -> Self {
+        Self::Free
+    }
+}
+
+impl fmt::Display for Aspect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Free => "free",
+                Self::Square => "1:1",
+                Self::FourThree => "4:3",
+                Self::SixteenNine => "16:9",
+            }
+        )
+    }
+}
+
+/// Crop rectangle in fractions of the rotated and flipped source size, so
+/// that it holds whatever the source resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Crop {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl Crop {
+    /// Rectangle spanning two opposite corners, in any order.
+    pub fn from_corners((x0, y0): (f32, f32), (x1, y1): (f32, f32)) -> Self {
+        let (x0, x1) = (x0.min(x1).max(0.0), x0.max(x1).min(1.0));
+        let (y0, y1) = (y0.min(y1).max(0.0), y0.max(y1).min(1.0));
+        Self {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        }
+    }
+
+    /// Pixel `(x, y, width, height)` of the rectangle within a `width` by
+    /// `height` image, shrunk from its bottom right to fit `aspect`.
+    pub fn pixels(&self, aspect: Aspect, width: usize, height: usize) -> [usize; 4] {
+        if width == 0 || height == 0 {
+            return [0, 0, width, height];
+        }
+
+        let x = ((self.x * width as f32) as usize).min(width.saturating_sub(1));
+        let y = ((self.y * height as f32) as usize).min(height.saturating_sub(1));
+        let mut w = ((self.width * width as f32).round() as usize).clamp(1, width - x);
+        let mut h = ((self.height * height as f32).round() as usize).clamp(1, height - y);
+
+        if let Some(ratio) = aspect.ratio() {
+            if w as f64 / h as f64 > ratio {
+                w = ((h as f64 * ratio).round() as usize).max(1);
+            } else {
+                h = ((w as f64 / ratio).round() as usize).max(1);
+            }
+        }
+
+        [x, y, w, h]
+    }
+}